@@ -1,7 +1,5 @@
 use std::io::{self, BufReader, Read, BufRead};
 
-mod intcode;
-
 #[derive(PartialEq, Debug, Copy, Clone)]
 struct Block {
     alignment_param: i32,
@@ -103,7 +101,7 @@ fn read_and_compute_by_line<T: Read>(reader: T) -> io::Result<()> {
 
     // The board is 61 by 61 matrix
     let mut computer = intcode::Computer::new(value_vec.clone());
-    computer.run();
+    computer.run_to_halt().unwrap();
 
     let mut board = Board::new();
 
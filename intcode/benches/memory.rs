@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use intcode::{Access, Computer, Memory, RunState};
+
+#[derive(Clone, Copy)]
+enum Op {
+    Read(i128),
+    Write(i128, i128)
+}
+
+fn load(day: &str) -> Vec<i128> {
+    let path = format!("{}/../{}/input", env!("CARGO_MANIFEST_DIR"), day);
+    intcode::parse_program(&fs::read_to_string(path).unwrap()).unwrap()
+}
+
+// Every memory access the program makes, in order: the instruction words it fetches and the data
+// cells it reads and writes
+fn access_log(program: &[i128], inputs: &[i128]) -> Vec<Op> {
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut computer = Computer::new(program.to_vec());
+    inputs.iter().for_each(|&input| computer.push_input(input));
+
+    let data_log = Arc::clone(&log);
+    computer.watch_with(0..=i128::MAX, Access::ReadWrite, move |event| {
+        let op = match event.access {
+            Access::Write => Op::Write(event.position, event.new),
+            _ => Op::Read(event.position)
+        };
+        data_log.lock().unwrap().push(op);
+    });
+
+    loop {
+        let ip = computer.instruction_pointer();
+        let instruction = Computer::get_instruction(ip, computer.peek(ip)).unwrap();
+        let words = 1 + Computer::get_number_parameters(&instruction.op_code) as i128;
+        log.lock().unwrap().extend((ip..ip + words).map(Op::Read));
+
+        match computer.step().unwrap() {
+            None | Some(RunState::Output(_)) => {},
+            Some(_) => break
+        }
+    }
+
+    let ops = log.lock().unwrap().clone();
+    ops
+}
+
+// What Computer did before the paged memory: one map, and reads insert the zero they return
+fn replay_hashmap(program: &[i128], log: &[Op]) -> i128 {
+    let mut memory: HashMap<i128, i128> = program.iter().enumerate().map(|(idx, value)| (idx as i128, *value)).collect();
+    let mut sum = 0;
+    for op in log {
+        match *op {
+            Op::Read(pos) => sum += *memory.entry(pos).or_insert(0),
+            Op::Write(pos, value) => { memory.insert(pos, value); }
+        }
+    }
+    sum
+}
+
+fn replay_paged(program: &[i128], log: &[Op]) -> i128 {
+    let mut memory = Memory::new(program.to_vec());
+    let mut sum = 0;
+    for op in log {
+        match *op {
+            Op::Read(pos) => sum += memory.get(pos),
+            Op::Write(pos, value) => memory.set(pos, value)
+        }
+    }
+    sum
+}
+
+fn bench_day(c: &mut Criterion, day: &str, inputs: &[i128]) {
+    let program = load(day);
+    let log = access_log(&program, inputs);
+
+    let mut group = c.benchmark_group(day);
+    group.bench_function("hashmap", |b| b.iter(|| replay_hashmap(black_box(&program), black_box(&log))));
+    group.bench_function("paged", |b| b.iter(|| replay_paged(black_box(&program), black_box(&log))));
+    group.bench_function("run", |b| b.iter(|| {
+        let mut computer = Computer::new(program.clone());
+        inputs.iter().for_each(|&input| computer.push_input(input));
+        computer.run().unwrap()
+    }));
+    group.finish();
+}
+
+fn memory_benches(c: &mut Criterion) {
+    bench_day(c, "day_9/p2", &[2]);
+    bench_day(c, "day_13/p1", &[]);
+}
+
+criterion_group!(benches, memory_benches);
+criterion_main!(benches);
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::{Computer, Instruction, OpCode, ParamModes};
+
+// Assembles the text format below into a program, one statement per line:
+//
+//     ; comments start with a semicolon
+//     start:  in [x]                  ; position operand
+//             add [x], #-1, rb+2      ; immediate and relative operands
+//             jt [x], #start          ; labels resolve to their address
+//             out [table+1]           ; and can take an offset
+//             hlt
+//     x:      .data 0, 42, start
+//     table:  .ascii "hi\n"
+//
+// Mnemonics and operand syntax are the ones the disassembler prints.
+#[derive(PartialEq, Debug, Clone)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+enum Value {
+    Number(i128),
+    Label(String, i128)
+}
+
+enum Statement {
+    Instruction(OpCode, Vec<(ParamModes, Value)>),
+    Data(Vec<Value>)
+}
+
+pub fn assemble(source: &str) -> Result<Vec<i128>, AsmError> {
+    let mut labels: HashMap<String, i128> = HashMap::new();
+    let mut statements: Vec<(usize, Statement)> = Vec::new();
+    let mut address: i128 = 0;
+
+    // First pass: parse every line and record where each label lands
+    for (idx, raw_line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+        let error = |message: String| AsmError { line: line_number, message };
+
+        let mut line = strip_comment(raw_line).trim();
+        while let Some((label, rest)) = split_label(line) {
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(error(format!("label '{}' defined twice", label)));
+            }
+            line = rest.trim();
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(line).map_err(error)?;
+        address += match &statement {
+            Statement::Instruction(_, operands) => 1 + operands.len() as i128,
+            Statement::Data(values) => values.len() as i128
+        };
+        statements.push((line_number, statement));
+    }
+
+    // Second pass: encode with every label known
+    let mut program = Vec::new();
+    for (line_number, statement) in statements {
+        let resolve = |value: &Value| -> Result<i128, AsmError> {
+            match value {
+                Value::Number(number) => Ok(*number),
+                Value::Label(label, offset) => labels.get(label)
+                    .map(|address| address + offset)
+                    .ok_or_else(|| AsmError { line: line_number, message: format!("unknown label '{}'", label) })
+            }
+        };
+
+        match statement {
+            Statement::Instruction(op_code, operands) => {
+                let param_modes = operands.iter().map(|(mode, _)| *mode).collect();
+                program.push(Instruction { op_code, param_modes }.code());
+                for (_, value) in operands.iter() {
+                    program.push(resolve(value)?);
+                }
+            },
+            Statement::Data(values) => {
+                for value in values.iter() {
+                    program.push(resolve(value)?);
+                }
+            }
+        }
+    }
+
+    Ok(program)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (idx, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..idx],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let colon = line.find(':')?;
+    let label = line[..colon].trim();
+
+    if is_identifier(label) {
+        Some((label, &line[colon + 1..]))
+    } else {
+        None
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false
+    }
+}
+
+fn parse_statement(line: &str) -> Result<Statement, String> {
+    let (keyword, rest) = match line.find(char::is_whitespace) {
+        Some(idx) => (&line[..idx], line[idx..].trim()),
+        None => (line, "")
+    };
+
+    match keyword {
+        ".data" => {
+            let values = split_operands(rest).iter()
+                .map(|value| parse_value(value))
+                .collect::<Result<Vec<Value>, String>>()?;
+            if values.is_empty() {
+                return Err(".data needs at least one value".to_string());
+            }
+            Ok(Statement::Data(values))
+        },
+        ".ascii" => {
+            let text = parse_string(rest)?;
+            Ok(Statement::Data(text.chars().map(|c| Value::Number(c as i128)).collect()))
+        },
+        mnemonic => {
+            let op_code = OpCode::from_mnemonic(mnemonic)
+                .ok_or_else(|| format!("unknown mnemonic '{}'", mnemonic))?;
+
+            let operands = split_operands(rest).iter()
+                .map(|operand| parse_operand(operand))
+                .collect::<Result<Vec<(ParamModes, Value)>, String>>()?;
+
+            let expected = Computer::get_number_parameters(&op_code) as usize;
+            if operands.len() != expected {
+                return Err(format!("{} takes {} operands, found {}", mnemonic, expected, operands.len()));
+            }
+
+            if let Some(idx) = op_code.write_parameter() {
+                if operands[idx].0 == ParamModes::ImmediateMode {
+                    return Err(format!("{} cannot write to an immediate operand", mnemonic));
+                }
+            }
+
+            Ok(Statement::Instruction(op_code, operands))
+        }
+    }
+}
+
+fn split_operands(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        text.split(',').map(|operand| operand.trim()).collect()
+    }
+}
+
+fn parse_operand(operand: &str) -> Result<(ParamModes, Value), String> {
+    if operand.starts_with('[') && operand.ends_with(']') {
+        Ok((ParamModes::PositionMode, parse_value(&operand[1..operand.len() - 1])?))
+    } else if let Some(value) = operand.strip_prefix('#') {
+        Ok((ParamModes::ImmediateMode, parse_value(value)?))
+    } else if let Some(offset) = operand.strip_prefix("rb") {
+        let offset = offset.trim();
+        if offset.is_empty() {
+            return Ok((ParamModes::RelativeMode, Value::Number(0)));
+        }
+
+        let value = match offset.strip_prefix('+') {
+            Some(value) => parse_value(value)?,
+            None if offset.starts_with('-') => parse_value(offset)?,
+            None => return Err(format!("bad relative operand '{}'", operand))
+        };
+        Ok((ParamModes::RelativeMode, value))
+    } else {
+        Err(format!("bad operand '{}', expected [pos], #imm or rb+off", operand))
+    }
+}
+
+// A number, a label, or a label plus/minus a number, which can have its own sign (label + -1)
+fn parse_value(text: &str) -> Result<Value, String> {
+    let text = text.trim();
+    if let Ok(number) = text.parse::<i128>() {
+        return Ok(Value::Number(number));
+    }
+
+    let (label, offset) = match text.find(['+', '-']) {
+        Some(idx) if idx > 0 => {
+            let offset = text[idx + 1..].trim().parse::<i128>().ok()
+                .and_then(|offset| if &text[idx..=idx] == "-" { offset.checked_neg() } else { Some(offset) })
+                .ok_or_else(|| format!("bad offset in '{}'", text))?;
+            (text[..idx].trim(), offset)
+        },
+        _ => (text, 0)
+    };
+
+    if is_identifier(label) {
+        Ok(Value::Label(label.to_string(), offset))
+    } else {
+        Err(format!("bad value '{}'", text))
+    }
+}
+
+fn parse_string(text: &str) -> Result<String, String> {
+    if text.len() < 2 || !text.starts_with('"') || !text.ends_with('"') {
+        return Err(format!("expected a quoted string, found '{}'", text));
+    }
+
+    let mut result = String::new();
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some('"') => result.push('"'),
+            Some(other) => return Err(format!("unknown escape '\\{}'", other)),
+            None => return Err("string ends with a lone backslash".to_string())
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(source: &str) -> AsmError {
+        assemble(source).unwrap_err()
+    }
+
+    #[test]
+    fn labels_can_be_used_before_they_are_defined() {
+        let program = assemble("
+                    jt #1, #end
+            value:  .data 7
+            end:    out [value]
+                    hlt
+        ").unwrap();
+        assert_eq!(program, vec![1105, 1, 4, 7, 4, 3, 99]);
+    }
+
+    #[test]
+    fn label_offsets() {
+        let program = assemble("
+            a:      .data a+1, a - 2, a + -1, a+ 3
+            b:      .data b - -1, rb
+            rb:     add rb+1, rb-1, rb+-2
+        ").unwrap();
+        assert_eq!(program, vec![1, -2, -1, 3, 5, 6, 22201, 1, -1, -2]);
+    }
+
+    #[test]
+    fn ascii_escapes() {
+        let program = assemble(r#"
+            .ascii "a;b\n\t\\\"" ; comment after the string
+        "#).unwrap();
+        assert_eq!(program, vec![97, 59, 98, 10, 9, 92, 34]);
+
+        assert_eq!(error(r#".ascii "\q""#).message, "unknown escape '\\q'");
+        assert_eq!(error(r#".ascii "a\""#).message, "string ends with a lone backslash");
+        assert_eq!(error(".ascii abc").message, "expected a quoted string, found 'abc'");
+    }
+
+    #[test]
+    fn duplicate_label() {
+        let err = error("x: .data 1\ny: .data 2\nx: .data 3");
+        assert_eq!(err, AsmError { line: 3, message: "label 'x' defined twice".to_string() });
+    }
+
+    #[test]
+    fn undefined_label() {
+        let err = error("hlt\n\n out [nowhere + 1]");
+        assert_eq!(err, AsmError { line: 3, message: "unknown label 'nowhere'".to_string() });
+    }
+
+    #[test]
+    fn bad_operands() {
+        let cases = [
+            ("jmp #1", "unknown mnemonic 'jmp'"),
+            ("add #1, #2", "add takes 3 operands, found 2"),
+            ("hlt #1", "hlt takes 0 operands, found 1"),
+            ("in #5", "in cannot write to an immediate operand"),
+            ("out 5", "bad operand '5', expected [pos], #imm or rb+off"),
+            ("out rb*2", "bad relative operand 'rb*2'"),
+            ("out [1x]", "bad value '1x'"),
+            ("out [x + y]", "bad offset in 'x + y'"),
+            (".data", ".data needs at least one value")
+        ];
+        for &(source, message) in cases.iter() {
+            assert_eq!(error(source), AsmError { line: 1, message: message.to_string() }, "{}", source);
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::io::{self, Read};
+
+// Assembles the source given on stdin and prints the program in the puzzle input format,
+// e.g. `asm < program.asm > input`
+fn main() {
+    let mut source = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut source) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+
+    match intcode::asm::assemble(&source) {
+        Ok(program) => {
+            let words: Vec<String> = program.iter().map(|word| word.to_string()).collect();
+            println!("{}", words.join(","));
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
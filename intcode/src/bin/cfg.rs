@@ -0,0 +1,20 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+// Prints the control flow graph of the program given on stdin in Graphviz format,
+// e.g. `cfg < ../day_15/p1/input | dot -Tsvg > day15.svg`
+fn main() {
+    if let Err(err) = read_and_graph(io::stdin()) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn read_and_graph<T: Read>(reader: T) -> io::Result<()> {
+    let buffer = BufReader::new(reader);
+    let input = buffer.lines().next().unwrap_or_else(|| Ok(String::new()))?;
+
+    let program = intcode::parse_program(&input)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    intcode::cfg::build(&program).write_dot(&mut io::stdout())
+}
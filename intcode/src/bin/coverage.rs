@@ -0,0 +1,43 @@
+use std::env;
+use std::io;
+
+use intcode::RunState;
+
+// Runs a program and shows which parts of it were used, e.g. `coverage ../day_5/p1/input 1`.
+// Each line of the listing is marked x (executed), r (read) and w (written). With --summary only
+// the totals are printed. The program's own output goes to stderr, away from the listing.
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> io::Result<()> {
+    let usage = || io::Error::new(io::ErrorKind::InvalidInput, "usage: coverage [--summary] <program file> [input...]");
+
+    let summary_only = env::args().any(|arg| arg == "--summary");
+    let args: Vec<String> = env::args().skip(1).filter(|arg| arg != "--summary").collect();
+
+    let path = args.first().ok_or_else(usage)?;
+    let mut computer = intcode::load_program_file(path, &args[1..])?;
+    // The listing is of the program as loaded, not as it ends up
+    let program = computer.dump_memory();
+
+    computer.enable_coverage();
+    let state = computer.run().map_err(io::Error::other)?;
+    while let Some(output) = computer.read_output() {
+        eprintln!("{}", output);
+    }
+    if state != RunState::Halted {
+        eprintln!("stopped with {:?}", state);
+    }
+
+    let coverage = computer.take_coverage().expect("coverage was enabled");
+    let mut out = io::stdout();
+    if !summary_only {
+        coverage.write_listing(&mut out, &program)?;
+        println!();
+    }
+    coverage.write_summary(&mut out, program.len())
+}
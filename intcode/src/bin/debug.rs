@@ -0,0 +1,34 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+
+use intcode::debugger::Debugger;
+
+// Interactive debugger, e.g. `debug ../day_9/p1/input`. Type help for the command list.
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> io::Result<()> {
+    let path = env::args().nth(1)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "usage: debug <program file>"))?;
+
+    let mut debugger = Debugger::new(intcode::load_program_file(&path, &[])?);
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    write!(out, "(icdb) ")?;
+    out.flush()?;
+    for line in stdin.lock().lines() {
+        if !debugger.execute(&line?, &mut out)? {
+            break;
+        }
+        write!(out, "(icdb) ")?;
+        out.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+// Prints the program given on stdin as pseudo-code, e.g. `decompile < ../day_13/p1/input`
+fn main() {
+    if let Err(err) = read_and_decompile(io::stdin()) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn read_and_decompile<T: Read>(reader: T) -> io::Result<()> {
+    let buffer = BufReader::new(reader);
+    let input = buffer.lines().next().unwrap_or_else(|| Ok(String::new()))?;
+
+    let program = intcode::parse_program(&input)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    print!("{}", intcode::decompile::decompile(&program));
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+// Prints a listing of the program given on stdin, e.g. `disasm < ../day_9/p1/input`
+fn main() {
+    if let Err(err) = read_and_disassemble(io::stdin()) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn read_and_disassemble<T: Read>(reader: T) -> io::Result<()> {
+    let buffer = BufReader::new(reader);
+    let input = buffer.lines().next().unwrap_or_else(|| Ok(String::new()))?;
+
+    let program = intcode::parse_program(&input)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    for line in intcode::disasm::disassemble(&program) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
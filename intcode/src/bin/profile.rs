@@ -0,0 +1,45 @@
+use std::env;
+use std::io;
+
+use intcode::RunState;
+
+// Runs a program with profiling on and prints where the time went, e.g. `profile --top 20 ../day_17/p1/input`.
+// The program's own output goes to stderr, away from the report.
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> io::Result<()> {
+    let usage = || io::Error::new(io::ErrorKind::InvalidInput, "usage: profile [--top N] <program file> [input...]");
+
+    let mut top = 15;
+    let mut args = Vec::new();
+    let mut raw_args = env::args().skip(1);
+    while let Some(arg) = raw_args.next() {
+        if arg == "--top" {
+            top = raw_args.next().and_then(|n| n.parse::<usize>().ok()).ok_or_else(usage)?;
+        } else {
+            args.push(arg);
+        }
+    }
+
+    let path = args.first().ok_or_else(usage)?;
+    let mut computer = intcode::load_program_file(path, &args[1..])?;
+    // Hot spots are shown as loaded, a program that rewrites itself would otherwise list what it left behind
+    let program = computer.dump_memory();
+
+    computer.enable_profile();
+    let state = computer.run().map_err(io::Error::other)?;
+    while let Some(output) = computer.read_output() {
+        eprintln!("{}", output);
+    }
+    if state != RunState::Halted {
+        eprintln!("stopped with {:?}", state);
+    }
+
+    let profile = computer.take_profile().expect("profiling was enabled");
+    profile.write_report(&mut io::stdout(), top, Some(&program))
+}
@@ -0,0 +1,43 @@
+use std::env;
+use std::io::{self, BufWriter};
+
+use intcode::{RunState, TraceFormat};
+
+// Runs a program and writes its execution trace to stdout, e.g. `trace ../day_9/p1/input 1 > day9.jsonl`.
+// Pass --binary for the compact format. Whatever the program prints goes to stderr.
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> io::Result<()> {
+    let mut format = TraceFormat::JsonLines;
+    let mut args = Vec::new();
+    for arg in env::args().skip(1) {
+        if arg == "--binary" {
+            format = TraceFormat::Binary;
+        } else {
+            args.push(arg);
+        }
+    }
+
+    let path = args.first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "usage: trace [--binary] <program file> [input...]"))?;
+    let mut computer = intcode::load_program_file(path, &args[1..])?;
+
+    computer.trace_to(BufWriter::new(io::stdout()), format);
+    let state = computer.run();
+    computer.finish_trace()?;
+
+    while let Some(output) = computer.read_output() {
+        eprintln!("{}", output);
+    }
+
+    match state {
+        Ok(RunState::Halted) => Ok(()),
+        Ok(state) => Err(io::Error::other(format!("stopped with {:?}", state))),
+        Err(err) => Err(io::Error::other(err))
+    }
+}
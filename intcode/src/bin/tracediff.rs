@@ -0,0 +1,46 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, BufReader};
+
+use intcode::trace::read_binary_trace;
+
+// Compares two binary traces and prints the first record where they diverge
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("usage: tracediff <trace> <trace>");
+        std::process::exit(2);
+    }
+
+    match diff(&args[0], &args[1]) {
+        Ok(true) => {},
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn diff(left: &str, right: &str) -> io::Result<bool> {
+    let left_trace = read_binary_trace(BufReader::new(File::open(left)?))?;
+    let right_trace = read_binary_trace(BufReader::new(File::open(right)?))?;
+
+    for (left_record, right_record) in left_trace.iter().zip(right_trace.iter()) {
+        if left_record != right_record {
+            println!("traces diverge at step {}", left_record.step);
+            println!("{}: {}", left, left_record.to_json());
+            println!("{}: {}", right, right_record.to_json());
+            return Ok(false);
+        }
+    }
+
+    if left_trace.len() != right_trace.len() {
+        println!("traces match for {} steps, then {} has {} and {} has {}",
+                 left_trace.len().min(right_trace.len()), left, left_trace.len(), right, right_trace.len());
+        return Ok(false);
+    }
+
+    println!("traces match ({} steps)", left_trace.len());
+    Ok(true)
+}
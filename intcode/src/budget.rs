@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+
+// Checking the clock every instruction would cost more than most instructions do
+pub(crate) const CLOCK_CHECK_INTERVAL: u64 = 1024;
+
+// Limits for a run, counted from when the budget is set. Memory is the image plus every page
+// allocated so far, so it grows in steps of a page.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct Budget {
+    pub max_steps: Option<u64>,
+    pub max_cells: Option<usize>,
+    pub timeout: Option<Duration>
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Limit {
+    Steps,
+    Memory,
+    Time
+}
+
+pub(crate) struct BudgetTracker {
+    pub(crate) budget: Budget,
+    start_steps: u64,
+    started: Instant
+}
+
+impl BudgetTracker {
+    pub(crate) fn new(budget: Budget, steps: u64) -> BudgetTracker {
+        BudgetTracker {
+            budget,
+            start_steps: steps,
+            started: Instant::now()
+        }
+    }
+
+    pub(crate) fn check(&self, steps: u64, cells: usize) -> Option<Limit> {
+        // Rewinding or restoring a snapshot can take steps back past where the budget started
+        let executed = steps.saturating_sub(self.start_steps);
+
+        if self.budget.max_steps.is_some_and(|max| executed >= max) {
+            return Some(Limit::Steps);
+        }
+
+        if self.budget.max_cells.is_some_and(|max| cells > max) {
+            return Some(Limit::Memory);
+        }
+
+        if let Some(timeout) = self.budget.timeout {
+            if executed.is_multiple_of(CLOCK_CHECK_INTERVAL) && self.started.elapsed() >= timeout {
+                return Some(Limit::Time);
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{OpCode, ParamModes};
+
+// Instruction decoded into a fixed size form so executing it needs no allocation. Modes past the
+// opcode's parameter count are PositionMode and never looked at.
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub(crate) struct Decoded {
+    pub(crate) op_code: OpCode,
+    pub(crate) modes: [ParamModes; 3]
+}
+
+// Decoded instructions by address, for the addresses of the program image. Decoding only depends
+// on the opcode word, so a write to an address drops the entry for that address and nothing else.
+pub(crate) struct DecodeCache {
+    entries: Vec<Option<Decoded>>
+}
+
+impl DecodeCache {
+    pub(crate) fn new(len: usize) -> DecodeCache {
+        DecodeCache {
+            entries: vec![None; len]
+        }
+    }
+
+    pub(crate) fn get(&self, address: i128) -> Option<Decoded> {
+        if address >= 0 && (address as usize) < self.entries.len() {
+            self.entries[address as usize]
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn insert(&mut self, address: i128, decoded: Decoded) {
+        if address >= 0 && (address as usize) < self.entries.len() {
+            self.entries[address as usize] = Some(decoded);
+        }
+    }
+
+    pub(crate) fn invalidate(&mut self, address: i128) {
+        if address >= 0 && (address as usize) < self.entries.len() {
+            self.entries[address as usize] = None;
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::disasm::{self, DisasmLine};
+use crate::{OpCode, ParamModes};
+
+// How control leaves a basic block
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Exit {
+    // Runs straight into the block starting at the given address
+    FallThrough(usize),
+    Jump(usize),
+    Branch { taken: usize, not_taken: usize },
+    // Return address stored in rb+0 right before an unconditional jump, the way the puzzle
+    // programs call subroutines
+    Call { target: usize, return_to: usize },
+    // Unconditional jump through a relative slot, how those subroutines come back
+    Return,
+    // Jump to an address only known at run time. Conditional ones may still fall through.
+    Indirect { not_taken: Option<usize> },
+    Halt,
+    // Ran into a word that does not decode or off the end of the program. Usually code that
+    // the program writes before running it.
+    Invalid
+}
+
+impl Exit {
+    // Blocks control can go to that are known statically
+    pub fn successors(&self) -> Vec<usize> {
+        match *self {
+            Exit::FallThrough(next) | Exit::Jump(next) => vec![next],
+            Exit::Branch { taken, not_taken } => vec![taken, not_taken],
+            Exit::Call { target, return_to } => vec![target, return_to],
+            Exit::Indirect { not_taken: Some(next) } => vec![next],
+            _ => Vec::new()
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub lines: Vec<DisasmLine>,
+    pub exit: Exit
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Cfg {
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    // Entry address of every subroutine, plus 0 for the program itself, with the blocks reachable
+    // from it without following calls
+    pub functions: BTreeMap<usize, BTreeSet<usize>>
+}
+
+// Splits everything reachable from address 0 into basic blocks. Only code that can be found
+// statically is included, so targets computed at run time leave parts of the program out.
+pub fn build(program: &[i128]) -> Cfg {
+    let mut lines = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    let mut calls = BTreeSet::new();
+    let mut pending = vec![0];
+
+    // First find every instruction and where blocks have to start
+    while let Some(entry) = pending.pop() {
+        if !leaders.insert(entry) || lines.contains_key(&entry) {
+            continue;
+        }
+
+        let mut address = entry;
+        let mut return_to = None;
+        while let Some(line) = disasm::decode_at(program, address) {
+            let exit = exit_of(&line, return_to);
+            return_to = stored_return(&line).or(if line_moves_rb(&line) { None } else { return_to });
+            let next = address + line.len();
+            lines.insert(address, line);
+
+            match exit {
+                None => {
+                    if lines.contains_key(&next) {
+                        break;
+                    }
+                    address = next;
+                },
+                Some(exit) => {
+                    if let Exit::Call { target, .. } = exit {
+                        calls.insert(target);
+                    }
+                    pending.extend(exit.successors());
+                    break;
+                }
+            }
+        }
+    }
+
+    // Then cut the instructions into blocks at the leaders and after every exit
+    let mut blocks = BTreeMap::new();
+    for &start in leaders.iter() {
+        if !lines.contains_key(&start) {
+            blocks.insert(start, BasicBlock { start, lines: Vec::new(), exit: Exit::Invalid });
+            continue;
+        }
+
+        let mut block_lines: Vec<DisasmLine> = Vec::new();
+        let mut address = start;
+        let mut return_to = None;
+        let exit = loop {
+            let line = match lines.get(&address) {
+                Some(line) => line.clone(),
+                None => break Exit::Invalid
+            };
+            let exit = exit_of(&line, return_to);
+            return_to = stored_return(&line).or(if line_moves_rb(&line) { None } else { return_to });
+            address += line.len();
+            block_lines.push(line);
+
+            if let Some(exit) = exit {
+                break exit;
+            }
+            if leaders.contains(&address) {
+                break Exit::FallThrough(address);
+            }
+        };
+
+        blocks.insert(start, BasicBlock { start, lines: block_lines, exit });
+    }
+
+    let mut functions = BTreeMap::new();
+    for entry in std::iter::once(0).chain(calls) {
+        functions.insert(entry, function_blocks(&blocks, entry));
+    }
+
+    Cfg {
+        blocks,
+        functions
+    }
+}
+
+impl Cfg {
+    pub fn write_dot<O: Write>(&self, out: &mut O) -> io::Result<()> {
+        writeln!(out, "digraph intcode {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+
+        // A block shared by several functions is only drawn in the first one
+        let mut drawn = BTreeSet::new();
+        for (entry, blocks) in self.functions.iter() {
+            writeln!(out, "    subgraph cluster_{} {{", entry)?;
+            let name = if *entry == 0 { "main".to_string() } else { format!("fn_{}", entry) };
+            writeln!(out, "        label=\"{}\";", name)?;
+            for start in blocks.iter().filter(|&&start| drawn.insert(start)) {
+                writeln!(out, "        b{} [label=\"{}\"];", start, self.label(*start))?;
+            }
+            writeln!(out, "    }}")?;
+        }
+        for start in self.blocks.keys().filter(|&&start| drawn.insert(start)) {
+            writeln!(out, "    b{} [label=\"{}\"];", start, self.label(*start))?;
+        }
+
+        let mut unknown = false;
+        for block in self.blocks.values() {
+            let from = block.start;
+            match block.exit {
+                Exit::FallThrough(to) | Exit::Jump(to) => writeln!(out, "    b{} -> b{};", from, to)?,
+                Exit::Branch { taken, not_taken } => {
+                    writeln!(out, "    b{} -> b{} [label=\"taken\"];", from, taken)?;
+                    writeln!(out, "    b{} -> b{} [style=dashed];", from, not_taken)?;
+                },
+                Exit::Call { target, return_to } => {
+                    writeln!(out, "    b{} -> b{} [label=\"call\", color=blue];", from, target)?;
+                    writeln!(out, "    b{} -> b{} [style=dotted];", from, return_to)?;
+                },
+                Exit::Indirect { not_taken } => {
+                    unknown = true;
+                    writeln!(out, "    b{} -> unknown [style=dashed];", from)?;
+                    if let Some(to) = not_taken {
+                        writeln!(out, "    b{} -> b{} [style=dashed];", from, to)?;
+                    }
+                },
+                Exit::Return | Exit::Halt | Exit::Invalid => {}
+            }
+        }
+        if unknown {
+            writeln!(out, "    unknown [shape=diamond, label=\"?\"];")?;
+        }
+
+        writeln!(out, "}}")
+    }
+
+    fn label(&self, start: usize) -> String {
+        let block = &self.blocks[&start];
+        let mut label: String = block.lines.iter()
+            .map(|line| format!("{}: {}\\l", line.address, escape(&line.text)))
+            .collect();
+
+        match block.exit {
+            Exit::Return => label.push_str("(return)\\l"),
+            Exit::Invalid => label.push_str("(invalid)\\l"),
+            _ => {}
+        }
+        label
+    }
+}
+
+// The exit an instruction ends its block with, None if execution just carries on. return_to is
+// the return address the block stored in rb+0 so far, if any.
+fn exit_of(line: &DisasmLine, return_to: Option<i128>) -> Option<Exit> {
+    let instruction = match &line.instruction {
+        Some(instruction) => instruction,
+        None => return Some(Exit::Invalid)
+    };
+    let next = line.address + line.len();
+
+    match instruction.op_code {
+        OpCode::Halt => Some(Exit::Halt),
+        OpCode::JIfTrue | OpCode::JIfFalse => {
+            let (condition_mode, condition) = (instruction.param_modes[0], line.words[1]);
+            let (target_mode, target) = (instruction.param_modes[1], line.words[2]);
+
+            // Immediate conditions are either always or never taken
+            let always = match condition_mode {
+                ParamModes::ImmediateMode => {
+                    let taken = (condition != 0) == (instruction.op_code == OpCode::JIfTrue);
+                    if !taken {
+                        return None;
+                    }
+                    true
+                },
+                _ => false
+            };
+
+            match (target_mode, always) {
+                (ParamModes::ImmediateMode, _) if target < 0 => Some(Exit::Invalid),
+                (ParamModes::ImmediateMode, true) => match return_to {
+                    Some(return_to) if return_to >= 0 => Some(Exit::Call { target: target as usize, return_to: return_to as usize }),
+                    _ => Some(Exit::Jump(target as usize))
+                },
+                (ParamModes::ImmediateMode, false) => Some(Exit::Branch { taken: target as usize, not_taken: next }),
+                (ParamModes::RelativeMode, true) => Some(Exit::Return),
+                (_, true) => Some(Exit::Indirect { not_taken: None }),
+                (_, false) => Some(Exit::Indirect { not_taken: Some(next) })
+            }
+        },
+        _ => None
+    }
+}
+
+// A constant an instruction stores in rb+0, i.e. add or mul with both inputs immediate
+pub(crate) fn stored_return(line: &DisasmLine) -> Option<i128> {
+    let instruction = line.instruction.as_ref()?;
+    let modes = &instruction.param_modes;
+    let value = match instruction.op_code {
+        OpCode::Add => line.words[1].checked_add(line.words[2]),
+        OpCode::Multiply => line.words[1].checked_mul(line.words[2]),
+        _ => return None
+    };
+
+    let constant = modes[0] == ParamModes::ImmediateMode && modes[1] == ParamModes::ImmediateMode;
+    if constant && modes[2] == ParamModes::RelativeMode && line.words[3] == 0 {
+        value
+    } else {
+        None
+    }
+}
+
+// After arb the slot a return address was stored in is no longer rb+0
+fn line_moves_rb(line: &DisasmLine) -> bool {
+    line.instruction.as_ref().is_some_and(|instruction| instruction.op_code == OpCode::SetRelOffset)
+}
+
+// Blocks reachable from entry, stepping over calls to their return address
+fn function_blocks(blocks: &BTreeMap<usize, BasicBlock>, entry: usize) -> BTreeSet<usize> {
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![entry];
+
+    while let Some(start) = pending.pop() {
+        let block = match blocks.get(&start) {
+            Some(block) if seen.insert(start) => block,
+            _ => continue
+        };
+
+        match block.exit {
+            Exit::Call { return_to, .. } => pending.push(return_to),
+            exit => pending.extend(exit.successors())
+        }
+    }
+
+    seen
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::asm;
+
+    const PROGRAM: &str = "
+                in [x]
+                jt [x], #skip
+                out #1
+        skip:   add #0, #back, rb+0
+                jt #1, #func
+        back:   hlt
+        func:   out [x]
+                jf #0, rb+0
+        x:      .data 0
+    ";
+
+    fn calls(day: &str) -> usize {
+        let input = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join(day).join("p1/input")).unwrap();
+        build(&crate::parse_program(input.trim()).unwrap()).blocks.values()
+            .filter(|block| matches!(block.exit, Exit::Call { .. }))
+            .count()
+    }
+
+    #[test]
+    fn blocks_and_edges() {
+        let cfg = build(&asm::assemble(PROGRAM).unwrap());
+
+        let blocks: Vec<(usize, usize, Exit)> = cfg.blocks.values()
+            .map(|block| (block.start, block.lines.len(), block.exit))
+            .collect();
+        assert_eq!(blocks, vec![
+            (0, 2, Exit::Branch { taken: 7, not_taken: 5 }),
+            (5, 1, Exit::FallThrough(7)),
+            (7, 2, Exit::Call { target: 15, return_to: 14 }),
+            (14, 1, Exit::Halt),
+            (15, 2, Exit::Return)
+        ]);
+        assert_eq!(cfg.blocks[&7].exit.successors(), vec![15, 14]);
+        assert!(cfg.blocks[&15].exit.successors().is_empty());
+    }
+
+    #[test]
+    fn functions_do_not_follow_calls() {
+        let cfg = build(&asm::assemble(PROGRAM).unwrap());
+        assert_eq!(cfg.functions, vec![
+            (0, vec![0, 5, 7, 14].into_iter().collect()),
+            (15, vec![15].into_iter().collect())
+        ].into_iter().collect());
+    }
+
+    #[test]
+    fn running_off_the_end() {
+        let cfg = build(&[1101, 1, 2, 3]);
+        assert_eq!(cfg.blocks[&0].exit, Exit::Invalid);
+    }
+
+    #[test]
+    fn calls_in_the_puzzle_programs() {
+        assert_eq!(calls("../day_9"), 3);
+        assert_eq!(calls("../day_13"), 14);
+        assert_eq!(calls("../day_17"), 20);
+    }
+}
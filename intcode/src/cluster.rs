@@ -0,0 +1,323 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::{Computer, IntcodeError, IntcodeIo, RunState, Word};
+
+// Which machine's output feeds which machine's input
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Topology {
+    // 0 -> 1 -> ... -> n-1
+    Chain,
+    // A chain whose last machine feeds the first again, like the day 7 feedback loop
+    Ring,
+    // The hub feeds every other machine and they all feed the hub
+    Star { hub: usize },
+    // Any set of (from, to) edges
+    Graph(Vec<(usize, usize)>)
+}
+
+impl Topology {
+    pub fn edges(&self, machines: usize) -> Vec<(usize, usize)> {
+        match self {
+            Topology::Chain => (1..machines).map(|to| (to - 1, to)).collect(),
+            Topology::Ring if machines == 0 => Vec::new(),
+            Topology::Ring => (0..machines).map(|from| (from, (from + 1) % machines)).collect(),
+            Topology::Star { hub } => (0..machines)
+                .filter(|leaf| leaf != hub)
+                .flat_map(|leaf| vec![(*hub, leaf), (leaf, *hub)])
+                .collect(),
+            Topology::Graph(edges) => edges.clone()
+        }
+    }
+}
+
+// Runs every machine on a thread of its own. Each output is sent to all the machines it has an
+// edge to, and machines nobody feeds only get what was pushed before the run.
+pub struct Cluster<W: Word = i128> {
+    machines: Vec<Computer<W>>,
+    edges: Vec<(usize, usize)>,
+    inputs: Vec<Vec<W>>
+}
+
+// How a cluster run ended. Outputs holds everything each machine printed, whether it went to
+// other machines or not.
+pub struct ClusterRun<W: Word = i128> {
+    pub machines: Vec<Computer<W>>,
+    pub states: Vec<Result<RunState<W>, IntcodeError>>,
+    pub outputs: Vec<Vec<W>>,
+    // Every machine still running was waiting for input nobody was going to send. Those are
+    // left in NeedsInput.
+    pub deadlocked: bool
+}
+
+impl<W: Word> ClusterRun<W> {
+    pub fn last_output(&self, machine: usize) -> Option<&W> {
+        self.outputs[machine].last()
+    }
+}
+
+enum Message<W> {
+    Value(W),
+    // Sent to everyone once the cluster is deadlocked
+    Stop
+}
+
+// Shared by all the machines to tell when none of them can make progress. Values are counted in
+// flight from when they are sent until they are taken off the receiving end, so a machine
+// waiting for a value already on its way does not count as stuck.
+struct Monitor<W> {
+    live: usize,
+    waiting: usize,
+    in_flight: usize,
+    stopped: Vec<bool>,
+    deadlocked: bool,
+    senders: Vec<Sender<Message<W>>>
+}
+
+impl<W> Monitor<W> {
+    fn check_deadlock(&mut self) {
+        if self.live > 0 && self.waiting == self.live && self.in_flight == 0 && !self.deadlocked {
+            self.deadlocked = true;
+            for (machine, sender) in self.senders.iter().enumerate() {
+                if !self.stopped[machine] {
+                    let _ = sender.send(Message::Stop);
+                }
+            }
+        }
+    }
+
+    // Sends under the monitor's lock so that the counts cannot miss a value
+    fn send(&mut self, to: usize, value: W) {
+        if !self.stopped[to] && self.senders[to].send(Message::Value(value)).is_ok() {
+            self.in_flight += 1;
+        }
+    }
+}
+
+struct NodeIo<W> {
+    id: usize,
+    input: Receiver<Message<W>>,
+    targets: Vec<usize>,
+    monitor: Arc<Mutex<Monitor<W>>>,
+    output: Vec<W>
+}
+
+impl<W: Word> NodeIo<W> {
+    fn received(&self, message: Message<W>) -> Option<W> {
+        match message {
+            Message::Value(value) => {
+                self.monitor.lock().unwrap().in_flight -= 1;
+                Some(value)
+            },
+            Message::Stop => None
+        }
+    }
+
+    // Called once the machine is done running, for whatever reason. Values still queued for it
+    // will never be read, so they stop counting as in flight.
+    fn leave(&self) {
+        let mut monitor = self.monitor.lock().unwrap();
+        monitor.stopped[self.id] = true;
+        monitor.live -= 1;
+        while let Ok(message) = self.input.try_recv() {
+            if let Message::Value(_) = message {
+                monitor.in_flight -= 1;
+            }
+        }
+        monitor.check_deadlock();
+    }
+}
+
+impl<W: Word> IntcodeIo<W> for NodeIo<W> {
+    fn read_input(&mut self) -> Option<W> {
+        if let Ok(message) = self.input.try_recv() {
+            return self.received(message);
+        }
+
+        {
+            let mut monitor = self.monitor.lock().unwrap();
+            monitor.waiting += 1;
+            monitor.check_deadlock();
+        }
+        let message = self.input.recv();
+        self.monitor.lock().unwrap().waiting -= 1;
+
+        // The monitor holds a sender for every machine, so this only fails if it is gone
+        message.ok().and_then(|message| self.received(message))
+    }
+
+    fn write_output(&mut self, value: W) {
+        let mut monitor = self.monitor.lock().unwrap();
+        for &to in self.targets.iter() {
+            monitor.send(to, value.clone());
+        }
+        self.output.push(value);
+    }
+}
+
+impl<W: Word> Cluster<W> {
+    // Panics if an edge refers to a machine that is not there
+    pub fn new(machines: Vec<Computer<W>>, topology: Topology) -> Cluster<W> {
+        let edges = topology.edges(machines.len());
+        for &(from, to) in edges.iter() {
+            assert!(from < machines.len() && to < machines.len(), "edge {} -> {} for {} machines", from, to, machines.len());
+        }
+
+        Cluster {
+            inputs: vec![Vec::new(); machines.len()],
+            machines,
+            edges
+        }
+    }
+
+    // Queued up for the machine before anything else it receives, e.g. day 7's phase settings
+    pub fn push_input(&mut self, machine: usize, value: W) {
+        self.inputs[machine].push(value);
+    }
+
+    // Blocks until every machine has halted, stopped on an error or watchpoint, or the whole
+    // cluster is stuck waiting for input
+    pub fn run(self) -> ClusterRun<W> {
+        let count = self.machines.len();
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..count).map(|_| mpsc::channel()).unzip();
+
+        let mut monitor = Monitor {
+            live: count,
+            waiting: 0,
+            in_flight: 0,
+            stopped: vec![false; count],
+            deadlocked: false,
+            senders
+        };
+        for (machine, inputs) in self.inputs.into_iter().enumerate() {
+            for value in inputs {
+                monitor.send(machine, value);
+            }
+        }
+        let monitor = Arc::new(Mutex::new(monitor));
+        let edges = self.edges;
+
+        let handles: Vec<_> = self.machines.into_iter()
+            .zip(receivers)
+            .enumerate()
+            .map(|(id, (mut machine, input))| {
+                let mut io = NodeIo {
+                    id,
+                    input,
+                    targets: edges.iter().filter(|&&(from, _)| from == id).map(|&(_, to)| to).collect(),
+                    monitor: Arc::clone(&monitor),
+                    output: Vec::new()
+                };
+
+                thread::spawn(move || {
+                    let state = machine.run_with(&mut io);
+                    io.leave();
+                    (machine, state, io.output)
+                })
+            })
+            .collect();
+
+        let mut run = ClusterRun {
+            machines: Vec::new(),
+            states: Vec::new(),
+            outputs: Vec::new(),
+            deadlocked: false
+        };
+        for handle in handles {
+            let (machine, state, output) = handle.join().expect("machine thread panicked");
+            run.machines.push(machine);
+            run.states.push(state);
+            run.outputs.push(output);
+        }
+        run.deadlocked = monitor.lock().unwrap().deadlocked;
+
+        run
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    fn machine(source: &str) -> Computer {
+        Computer::new(asm::assemble(source).unwrap())
+    }
+
+    #[test]
+    fn everyone_waiting_is_a_deadlock() {
+        let machines = vec![Computer::new(vec![3, 0, 99]), Computer::new(vec![3, 0, 99])];
+        let run = Cluster::new(machines, Topology::Ring).run();
+
+        assert!(run.deadlocked);
+        assert_eq!(run.states, vec![Ok(RunState::NeedsInput), Ok(RunState::NeedsInput)]);
+    }
+
+    #[test]
+    fn deadlock_after_the_others_halted() {
+        let machines = vec![Computer::new(vec![104, 1, 99]), Computer::new(vec![3, 0, 3, 0, 99])];
+        let run = Cluster::new(machines, Topology::Chain).run();
+
+        assert!(run.deadlocked);
+        assert_eq!(run.states, vec![Ok(RunState::Halted), Ok(RunState::NeedsInput)]);
+        assert_eq!(run.machines[1].peek(0), 1);
+    }
+
+    #[test]
+    fn star_sends_the_hub_output_to_every_leaf() {
+        let hub = "
+                in [a]
+                out [a]
+                in [a]
+                in [b]
+                add [a], [b], [a]
+                out [a]
+                hlt
+        a:      .data 0
+        b:      .data 0
+        ";
+        let leaf = "
+                in [x]
+                mul [x], #10, [x]
+                out [x]
+                hlt
+        x:      .data 0
+        ";
+
+        let mut cluster = Cluster::new(vec![machine(leaf), machine(hub), machine(leaf)], Topology::Star { hub: 1 });
+        cluster.push_input(1, 5);
+        let run = cluster.run();
+
+        assert!(!run.deadlocked);
+        assert!(run.states.iter().all(|state| *state == Ok(RunState::Halted)));
+        assert_eq!(run.outputs, vec![vec![50], vec![5, 100], vec![50]]);
+    }
+
+    #[test]
+    fn graph_uses_the_given_edges() {
+        let machines = vec![
+            Computer::new(vec![104, 3, 99]),
+            Computer::new(vec![104, 4, 99]),
+            machine("in [a]\nin [b]\nmul [a], [b], [a]\nout [a]\nhlt\na: .data 0\nb: .data 0")
+        ];
+        let run = Cluster::new(machines, Topology::Graph(vec![(0, 2), (1, 2)])).run();
+
+        assert!(!run.deadlocked);
+        assert_eq!(run.last_output(2), Some(&12));
+    }
+
+    #[test]
+    fn topology_edges() {
+        assert_eq!(Topology::Chain.edges(3), vec![(0, 1), (1, 2)]);
+        assert_eq!(Topology::Ring.edges(3), vec![(0, 1), (1, 2), (2, 0)]);
+        assert_eq!(Topology::Ring.edges(0), Vec::new());
+        assert_eq!(Topology::Star { hub: 1 }.edges(3), vec![(1, 0), (0, 1), (1, 2), (2, 1)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn edges_must_name_machines() {
+        Cluster::new(vec![Computer::new(vec![99])], Topology::Graph(vec![(0, 1)]));
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::disasm::{self, DisasmLine};
+use crate::{Computer, OpCode};
+
+// What happened to each address while coverage was on. Instructions are keyed by the address they
+// start at, with their length, so the parameter words count as executed too.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct Coverage {
+    pub instructions: BTreeMap<i128, usize>,
+    pub read: BTreeSet<i128>,
+    pub written: BTreeSet<i128>
+}
+
+// How one program cell was used
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct CellUse {
+    pub executed: bool,
+    pub read: bool,
+    pub written: bool
+}
+
+impl CellUse {
+    pub fn is_untouched(&self) -> bool {
+        !self.executed && !self.read && !self.written
+    }
+
+    // Three columns: x for executed, r for read, w for written
+    pub fn marker(&self) -> String {
+        let flag = |set: bool, c: char| if set { c } else { '.' };
+        [flag(self.executed, 'x'), flag(self.read, 'r'), flag(self.written, 'w')].iter().collect()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    pub(crate) fn record_instruction(&mut self, address: i128, op_code: OpCode) {
+        self.instructions.insert(address, Computer::get_number_parameters(&op_code) as usize + 1);
+    }
+
+    pub fn cell(&self, address: i128) -> CellUse {
+        // Only the closest instruction starting at or before address can cover it, overlapping
+        // instructions aside
+        let executed = self.instructions.range(..=address)
+            .next_back()
+            .is_some_and(|(&start, &len)| address < start + len as i128);
+
+        CellUse {
+            executed,
+            read: self.read.contains(&address),
+            written: self.written.contains(&address)
+        }
+    }
+
+    // Walks the program decoding from every executed instruction and listing everything else one
+    // word at a time, so data between code does not throw the listing off. Code the program rewrote
+    // before running it does not decode to what ran, so it is listed as data with a note.
+    pub fn listing(&self, program: &[i128]) -> Vec<(CellUse, DisasmLine)> {
+        let mut lines = Vec::new();
+        let mut address = 0;
+
+        while address < program.len() {
+            let line = match self.instructions.get(&(address as i128)) {
+                Some(&len) => {
+                    match disasm::decode_at(program, address) {
+                        Some(line) if line.len() == len && line.instruction.is_some() => line,
+                        _ => {
+                            let mut line = data_line(program, address);
+                            line.text.push_str("  ; rewritten before it ran");
+                            line
+                        }
+                    }
+                },
+                None => data_line(program, address)
+            };
+
+            let cell = self.cell(address as i128);
+            address += line.len();
+            lines.push((cell, line));
+        }
+
+        lines
+    }
+
+    pub fn write_listing<O: Write>(&self, out: &mut O, program: &[i128]) -> io::Result<()> {
+        for (cell, line) in self.listing(program) {
+            writeln!(out, "{} {}", cell.marker(), line)?;
+        }
+
+        Ok(())
+    }
+
+    // Counts are over the len cells of the program, anything touched past its end is reported apart
+    pub fn write_summary<O: Write>(&self, out: &mut O, len: usize) -> io::Result<()> {
+        let cells: Vec<CellUse> = (0..len as i128).map(|address| self.cell(address)).collect();
+        let percent = |count: usize| 100.0 * count as f64 / len.max(1) as f64;
+
+        let executed = cells.iter().filter(|cell| cell.executed).count();
+        let read = cells.iter().filter(|cell| cell.read).count();
+        let written = cells.iter().filter(|cell| cell.written).count();
+        let untouched = cells.iter().filter(|cell| cell.is_untouched()).count();
+
+        writeln!(out, "cells:     {:>6}", len)?;
+        writeln!(out, "executed:  {:>6} {:>6.2}%", executed, percent(executed))?;
+        writeln!(out, "read:      {:>6} {:>6.2}%", read, percent(read))?;
+        writeln!(out, "written:   {:>6} {:>6.2}%", written, percent(written))?;
+        writeln!(out, "untouched: {:>6} {:>6.2}%", untouched, percent(untouched))?;
+
+        let outside: BTreeSet<&i128> = self.read.iter()
+            .chain(self.written.iter())
+            .filter(|&&address| address < 0 || address >= len as i128)
+            .collect();
+        if !outside.is_empty() {
+            writeln!(out, "data cells used past the program: {}", outside.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+fn data_line(program: &[i128], address: usize) -> DisasmLine {
+    DisasmLine {
+        address,
+        words: vec![program[address]],
+        instruction: None,
+        text: format!(".data {}", program[address])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    // With input 1 the output is skipped, with 0 it runs
+    const PROGRAM: &str = "
+                in [x]
+                jt [x], #skip
+                out #1
+        skip:   out [x]
+                hlt
+        x:      .data 0
+    ";
+
+    fn covered(input: i128) -> (Vec<i128>, Coverage) {
+        let program = asm::assemble(PROGRAM).unwrap();
+        let mut computer = Computer::new(program.clone());
+        computer.enable_coverage();
+        computer.push_input(input);
+        computer.run_to_halt().unwrap();
+        (program, computer.take_coverage().unwrap())
+    }
+
+    fn executed(coverage: &Coverage, len: usize) -> BTreeSet<i128> {
+        (0..len as i128).filter(|&address| coverage.cell(address).executed).collect()
+    }
+
+    #[test]
+    fn branch_not_taken_is_uncovered() {
+        let (program, coverage) = covered(1);
+
+        assert_eq!(coverage.instructions, vec![(0, 2), (2, 3), (7, 2), (9, 1)].into_iter().collect());
+        assert_eq!(executed(&coverage, program.len()), vec![0, 1, 2, 3, 4, 7, 8, 9].into_iter().collect());
+        assert_eq!(coverage.read, vec![10].into_iter().collect());
+        assert_eq!(coverage.written, vec![10].into_iter().collect());
+
+        let untouched: Vec<i128> = (0..program.len() as i128).filter(|&address| coverage.cell(address).is_untouched()).collect();
+        assert_eq!(untouched, vec![5, 6]);
+    }
+
+    #[test]
+    fn every_branch_taken() {
+        let (program, coverage) = covered(0);
+        assert_eq!(executed(&coverage, program.len()), (0..10).collect());
+        assert_eq!(coverage.cell(10), CellUse { executed: false, read: true, written: true });
+    }
+
+    // The listing is of the program as loaded, not what the run left in memory
+    #[test]
+    fn listing_marks_each_line() {
+        let (program, coverage) = covered(1);
+        let markers: Vec<(usize, String, String)> = coverage.listing(&program).into_iter()
+            .map(|(cell, line)| (line.address, cell.marker(), line.text))
+            .collect();
+
+        assert_eq!(markers, vec![
+            (0, "x..".to_string(), "in [10]".to_string()),
+            (2, "x..".to_string(), "jt [10], #7".to_string()),
+            (5, "...".to_string(), ".data 104".to_string()),
+            (6, "...".to_string(), ".data 1".to_string()),
+            (7, "x..".to_string(), "out [10]".to_string()),
+            (9, "x..".to_string(), "hlt".to_string()),
+            (10, ".rw".to_string(), ".data 0".to_string())
+        ]);
+    }
+
+    #[test]
+    fn summary_counts() {
+        let (program, coverage) = covered(1);
+        let mut summary = Vec::new();
+        coverage.write_summary(&mut summary, program.len()).unwrap();
+        let summary = String::from_utf8(summary).unwrap();
+
+        assert!(summary.starts_with("cells:         11\nexecuted:       8  72.73%\nread:           1   9.09%\n"), "{}", summary);
+        assert!(summary.contains("untouched:      2  18.18%\n"), "{}", summary);
+    }
+}
@@ -0,0 +1,466 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+
+use crate::disasm::{self, DisasmLine};
+use crate::{Access, Computer, IntcodeError, OpCode, RunState, Snapshot};
+
+const HELP: &str = "\
+commands:
+  s, step [n]            execute n instructions (default 1)
+  c, continue            run until a breakpoint, input is needed or the program halts
+  b, break <addr|op>     break before the instruction at addr, or before any instruction with mnemonic op
+  d, delete <addr|op>    remove a breakpoint
+  back [n]               undo the last n instructions (default 1)
+  lastwrite <pos>        go back to just before the last instruction that wrote pos
+  w, watch <pos>[-<end>] [r|w|rw]
+                         pause after the program reads and/or writes the given memory (default w)
+  unwatch <id>           remove a watchpoint
+  info                   list breakpoints and watchpoints
+  r, regs                show instruction pointer, relative base and the input/output queues
+  l, list [addr] [n]     disassemble n instructions from addr (default: the instruction pointer)
+  x <addr> [n]           examine n memory cells from addr
+  set <addr> <value>     write value to memory
+  i, input <v>...        queue input values
+  o, output              print and drain the output queue
+  save <file>            write the machine state to file
+  load <file>            resume from a state written by save
+  q, quit                leave the debugger";
+
+// How many instructions back can be undone
+const HISTORY_LIMIT: usize = 1_000_000;
+
+// Why a step or continue gave control back to the user
+enum Stop {
+    Breakpoint(i128),
+    OpBreakpoint(OpCode),
+    State(RunState),
+    Error(IntcodeError),
+    Stepped
+}
+
+pub struct Debugger {
+    computer: Computer,
+    breakpoints: HashSet<i128>,
+    op_breakpoints: HashSet<OpCode>
+}
+
+impl Debugger {
+    pub fn new(mut computer: Computer) -> Debugger {
+        computer.enable_history(HISTORY_LIMIT);
+
+        Debugger {
+            computer,
+            breakpoints: HashSet::new(),
+            op_breakpoints: HashSet::new()
+        }
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    // Runs one command line. Returns false once the user asked to quit.
+    pub fn execute(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Ok(true)
+        };
+
+        match command {
+            "s" | "step" => {
+                let count = match args.first() {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(count) => count,
+                        Err(_) => return writeln!(out, "bad step count '{}'", arg).map(|_| true)
+                    },
+                    None => 1
+                };
+                let stop = self.resume(Some(count));
+                self.report(stop, out)?;
+            },
+            "c" | "continue" => {
+                let stop = self.resume(None);
+                self.report(stop, out)?;
+            },
+            "b" | "break" => match args.first() {
+                Some(arg) => self.set_breakpoint(arg, true, out)?,
+                None => writeln!(out, "break needs an address or a mnemonic")?
+            },
+            "d" | "delete" => match args.first() {
+                Some(arg) => self.set_breakpoint(arg, false, out)?,
+                None => writeln!(out, "delete needs an address or a mnemonic")?
+            },
+            "back" => {
+                let count = match args.first() {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(count) => count,
+                        Err(_) => return writeln!(out, "bad step count '{}'", arg).map(|_| true)
+                    },
+                    None => 1
+                };
+                let rewound = self.computer.rewind(count);
+                writeln!(out, "went back {} instructions", rewound)?;
+                self.print_line(self.computer.instruction_pointer(), out)?;
+            },
+            "lastwrite" => match parse_arg(args.first(), -1) {
+                Some(pos) if pos >= 0 => match self.computer.rewind_to_last_write(pos) {
+                    Some(step) => {
+                        writeln!(out, "back at step {}", step)?;
+                        self.print_line(self.computer.instruction_pointer(), out)?;
+                    },
+                    None => writeln!(out, "no recorded write to {}", pos)?
+                },
+                _ => writeln!(out, "lastwrite needs a non negative position")?
+            },
+            "w" | "watch" => self.add_watchpoint(args, out)?,
+            "unwatch" => match args.first().and_then(|id| id.parse::<usize>().ok()) {
+                Some(id) if self.computer.unwatch(id) => {},
+                Some(id) => writeln!(out, "no watchpoint {}", id)?,
+                None => writeln!(out, "unwatch needs a watchpoint id")?
+            },
+            "info" => self.print_breakpoints(out)?,
+            "r" | "regs" => self.print_registers(out)?,
+            "l" | "list" => {
+                let address = match parse_arg(args.first(), self.computer.instruction_pointer()) {
+                    Some(address) if address >= 0 => address,
+                    _ => return writeln!(out, "bad address").map(|_| true)
+                };
+                let count = parse_arg(args.get(1), 10).unwrap_or(10);
+                self.print_listing(address, count, out)?;
+            },
+            "x" => {
+                let address = match parse_arg(args.first(), -1) {
+                    Some(address) if address >= 0 => address,
+                    _ => return writeln!(out, "x needs a non negative address").map(|_| true)
+                };
+                let count = parse_arg(args.get(1), 1).unwrap_or(1);
+                match address.checked_add(count) {
+                    Some(end) => self.print_memory(address, end, out)?,
+                    None => writeln!(out, "{} cells from {} go past the last address", count, address)?
+                }
+            },
+            "set" => {
+                match (parse_arg(args.first(), -1), args.get(1).and_then(|v| v.parse::<i128>().ok())) {
+                    (Some(address), Some(value)) if address >= 0 => match self.computer.poke(address, value) {
+                        Ok(()) => writeln!(out, "[{}] = {}", address, value)?,
+                        Err(err) => writeln!(out, "{}", err)?
+                    },
+                    _ => writeln!(out, "usage: set <addr> <value>")?
+                }
+            },
+            "i" | "input" => {
+                let values: Result<Vec<i128>, _> = args.iter().map(|v| v.parse::<i128>()).collect();
+                match values {
+                    Ok(values) => values.into_iter().for_each(|v| self.computer.push_input(v)),
+                    Err(_) => writeln!(out, "input values must be numbers")?
+                }
+            },
+            "o" | "output" => {
+                let mut values = Vec::new();
+                while let Some(value) = self.computer.read_output() {
+                    values.push(value.to_string());
+                }
+                writeln!(out, "{}", values.join(" "))?;
+            },
+            "save" => match args.first() {
+                Some(path) => {
+                    let result = File::create(path).and_then(|mut file| self.computer.snapshot().write_to(&mut file));
+                    if let Err(err) = result {
+                        writeln!(out, "could not save to {}: {}", path, err)?;
+                    }
+                },
+                None => writeln!(out, "save needs a file name")?
+            },
+            "load" => match args.first() {
+                Some(path) => match File::open(path).and_then(|file| Snapshot::read_from(BufReader::new(file))) {
+                    Ok(snapshot) => {
+                        self.computer.restore(&snapshot);
+                        self.print_line(self.computer.instruction_pointer(), out)?;
+                    },
+                    Err(err) => writeln!(out, "could not load {}: {}", path, err)?
+                },
+                None => writeln!(out, "load needs a file name")?
+            },
+            "h" | "help" => writeln!(out, "{}", HELP)?,
+            "q" | "quit" => return Ok(false),
+            _ => writeln!(out, "unknown command '{}', try help", command)?
+        }
+
+        Ok(true)
+    }
+
+    // Steps until max_steps instructions ran or something stops the machine. Breakpoints are checked
+    // before every instruction except the first, so continuing from a breakpoint makes progress.
+    fn resume(&mut self, max_steps: Option<usize>) -> Stop {
+        let mut executed = 0;
+
+        loop {
+            if max_steps == Some(executed) {
+                return Stop::Stepped;
+            }
+
+            if executed > 0 {
+                let address = self.computer.instruction_pointer();
+                if self.breakpoints.contains(&address) {
+                    return Stop::Breakpoint(address);
+                }
+
+                if let Some(op_code) = self.current_op_code() {
+                    if self.op_breakpoints.contains(&op_code) {
+                        return Stop::OpBreakpoint(op_code);
+                    }
+                }
+            }
+
+            match self.computer.step() {
+                Ok(None) => executed += 1,
+                Ok(Some(state)) => return Stop::State(state),
+                Err(err) => return Stop::Error(err)
+            }
+        }
+    }
+
+    fn report(&self, stop: Stop, out: &mut dyn Write) -> io::Result<()> {
+        match stop {
+            Stop::Breakpoint(address) => writeln!(out, "breakpoint at {}", address)?,
+            Stop::OpBreakpoint(op_code) => writeln!(out, "breakpoint on {}", op_code.mnemonic())?,
+            Stop::State(RunState::NeedsInput) => writeln!(out, "waiting for input")?,
+            Stop::State(RunState::Halted) => writeln!(out, "halted")?,
+            Stop::State(RunState::BudgetExceeded(limit)) => writeln!(out, "budget exceeded: {:?}", limit)?,
+            Stop::State(RunState::Output(value)) => writeln!(out, "output {}", value)?,
+            Stop::State(RunState::Watchpoint(events)) => {
+                for event in events {
+                    match event.access {
+                        Access::Read => writeln!(out, "watchpoint {}: [{}] read {} by instruction at {}",
+                                                 event.id, event.position, event.new, event.address)?,
+                        _ => writeln!(out, "watchpoint {}: [{}] {} -> {} by instruction at {}",
+                                      event.id, event.position, event.old, event.new, event.address)?
+                    }
+                }
+            },
+            Stop::Error(err) => writeln!(out, "error: {}", err)?,
+            Stop::Stepped => {}
+        }
+
+        self.print_line(self.computer.instruction_pointer(), out)
+    }
+
+    fn set_breakpoint(&mut self, arg: &str, enable: bool, out: &mut dyn Write) -> io::Result<()> {
+        if let Ok(address) = arg.parse::<i128>() {
+            if enable {
+                self.breakpoints.insert(address);
+            } else if !self.breakpoints.remove(&address) {
+                writeln!(out, "no breakpoint at {}", address)?;
+            }
+        } else if let Some(op_code) = OpCode::from_mnemonic(arg) {
+            if enable {
+                self.op_breakpoints.insert(op_code);
+            } else if !self.op_breakpoints.remove(&op_code) {
+                writeln!(out, "no breakpoint on {}", arg)?;
+            }
+        } else {
+            writeln!(out, "'{}' is neither an address nor a mnemonic", arg)?;
+        }
+
+        Ok(())
+    }
+
+    fn add_watchpoint(&mut self, args: &[&str], out: &mut dyn Write) -> io::Result<()> {
+        let positions = args.first().and_then(|range| {
+            let mut bounds = range.splitn(2, '-').map(|bound| bound.parse::<i128>().ok());
+            let start = bounds.next()??;
+            let end = bounds.next().unwrap_or(Some(start))?;
+            Some(start..=end)
+        });
+
+        let access = match args.get(1).copied() {
+            Some("r") => Some(Access::Read),
+            Some("w") | None => Some(Access::Write),
+            Some("rw") => Some(Access::ReadWrite),
+            Some(_) => None
+        };
+
+        match (positions, access) {
+            (Some(positions), Some(access)) => {
+                let id = self.computer.watch(positions, access);
+                writeln!(out, "watchpoint {}", id)
+            },
+            _ => writeln!(out, "usage: watch <pos>[-<end>] [r|w|rw]")
+        }
+    }
+
+    fn print_breakpoints(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut addresses: Vec<&i128> = self.breakpoints.iter().collect();
+        addresses.sort();
+        for address in addresses {
+            writeln!(out, "break at {}", address)?;
+        }
+
+        let mut mnemonics: Vec<&str> = self.op_breakpoints.iter().map(|op| op.mnemonic()).collect();
+        mnemonics.sort();
+        for mnemonic in mnemonics {
+            writeln!(out, "break on {}", mnemonic)?;
+        }
+
+        for watchpoint in self.computer.watchpoints() {
+            writeln!(out, "watch {}: {}-{} {:?}", watchpoint.id, watchpoint.positions.start(),
+                     watchpoint.positions.end(), watchpoint.access)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_registers(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "ip: {}", self.computer.instruction_pointer())?;
+        writeln!(out, "rb: {}", self.computer.relative_base())?;
+        writeln!(out, "input: {:?}", self.computer.pending_input())?;
+        writeln!(out, "output: {:?}", self.computer.pending_output())
+    }
+
+    fn print_listing(&self, address: i128, count: i128, out: &mut dyn Write) -> io::Result<()> {
+        let mut address = Some(address);
+        for _ in 0..count {
+            let line = match address.and_then(|address| self.line_at(address)) {
+                Some(line) => line,
+                None => break
+            };
+            address = address.and_then(|address| address.checked_add(line.len() as i128));
+            writeln!(out, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    // Cells from address up to but not including end
+    fn print_memory(&self, address: i128, end: i128, out: &mut dyn Write) -> io::Result<()> {
+        for row in (address..end).step_by(8) {
+            let values: Vec<String> = (row..row.saturating_add(8).min(end))
+                .map(|pos| self.computer.peek(pos).to_string())
+                .collect();
+            writeln!(out, "{:>6}: {}", row, values.join(" "))?;
+        }
+
+        Ok(())
+    }
+
+    fn print_line(&self, address: i128, out: &mut dyn Write) -> io::Result<()> {
+        match self.line_at(address) {
+            Some(line) => writeln!(out, "{}", line),
+            None => writeln!(out, "{:>6}  not an address", address)
+        }
+    }
+
+    // None for addresses memory cannot have, e.g. an instruction pointer a jump made negative
+    fn line_at(&self, address: i128) -> Option<DisasmLine> {
+        let start = usize::try_from(address).ok()?;
+        // Long enough for the widest instruction
+        let words: Vec<i128> = (address..address.saturating_add(4)).map(|pos| self.computer.peek(pos)).collect();
+        disasm::decode_words(start, &words)
+    }
+
+    fn current_op_code(&self) -> Option<OpCode> {
+        let address = self.computer.instruction_pointer();
+        Computer::get_instruction(address, self.computer.peek(address))
+            .ok()
+            .map(|instruction| instruction.op_code)
+    }
+}
+
+fn parse_arg(arg: Option<&&str>, default: i128) -> Option<i128> {
+    match arg {
+        Some(arg) => arg.parse::<i128>().ok(),
+        None => Some(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Adds up inputs until it reads a zero, then prints the sum
+    fn debugger() -> Debugger {
+        Debugger::new(Computer::new(crate::asm::assemble("
+            again:  in [x]
+                    jf [x], #done
+                    add [sum], [x], [sum]
+                    jt #1, #again
+            done:   out [sum]
+                    hlt
+            x:      .data 0
+            sum:    .data 0
+        ").unwrap()))
+    }
+
+    fn run(debugger: &mut Debugger, line: &str) -> String {
+        let mut out = Vec::new();
+        assert!(debugger.execute(line, &mut out).unwrap());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn step() {
+        let mut debugger = debugger();
+        run(&mut debugger, "input 5 7 0");
+        assert_eq!(run(&mut debugger, "s"), "     2  1006,15,12                   jf [15], #12\n");
+        assert_eq!(run(&mut debugger, "step 2"), "     9  1105,1,0                     jt #1, #0\n");
+        assert_eq!(run(&mut debugger, "s x"), "bad step count 'x'\n");
+    }
+
+    #[test]
+    fn continue_until_input_is_needed() {
+        let mut debugger = debugger();
+        assert_eq!(run(&mut debugger, "c"), "waiting for input\n     0  3,15                         in [15]\n");
+        run(&mut debugger, "i 0");
+        assert_eq!(run(&mut debugger, "continue"), "halted\n    14  99                           hlt\n");
+        assert_eq!(run(&mut debugger, "o"), "0\n");
+    }
+
+    #[test]
+    fn break_at_an_address_and_on_a_mnemonic() {
+        let mut debugger = debugger();
+        run(&mut debugger, "i 5 7 0");
+        run(&mut debugger, "b 12");
+        run(&mut debugger, "b add");
+        assert_eq!(run(&mut debugger, "info"), "break at 12\nbreak on add\n");
+
+        assert_eq!(run(&mut debugger, "c"), "breakpoint on add\n     5  1,16,15,16                   add [16], [15], [16]\n");
+        assert_eq!(run(&mut debugger, "c"), "breakpoint on add\n     5  1,16,15,16                   add [16], [15], [16]\n");
+        assert_eq!(run(&mut debugger, "d add"), "");
+        assert_eq!(run(&mut debugger, "c"), "breakpoint at 12\n    12  4,16                         out [16]\n");
+        assert_eq!(run(&mut debugger, "c"), "halted\n    14  99                           hlt\n");
+        assert_eq!(run(&mut debugger, "o"), "12\n");
+
+        assert_eq!(run(&mut debugger, "d 3"), "no breakpoint at 3\n");
+        assert_eq!(run(&mut debugger, "b nowhere"), "'nowhere' is neither an address nor a mnemonic\n");
+    }
+
+    #[test]
+    fn print_memory() {
+        let mut debugger = debugger();
+        assert_eq!(run(&mut debugger, "x 0 10"), "     0: 3 15 1006 15 12 1 16 15\n     8: 16 1105\n");
+        assert_eq!(run(&mut debugger, "set 16 42"), "[16] = 42\n");
+        assert_eq!(run(&mut debugger, "x 16"), "    16: 42\n");
+        assert_eq!(run(&mut debugger, "x -1"), "x needs a non negative address\n");
+        assert_eq!(run(&mut debugger, &format!("x {} 2", i128::MAX)),
+                   format!("2 cells from {} go past the last address\n", i128::MAX));
+        assert_eq!(run(&mut debugger, &format!("x {} 1", i128::MAX - 1)), format!("{:>6}: 0\n", i128::MAX - 1));
+    }
+
+    #[test]
+    fn list() {
+        let mut debugger = debugger();
+        assert_eq!(run(&mut debugger, "l 9 2"), "     9  1105,1,0                     jt #1, #0\n    12  4,16                         out [16]\n");
+        assert_eq!(run(&mut debugger, "l -3"), "bad address\n");
+    }
+
+    #[test]
+    fn negative_instruction_pointer() {
+        let mut debugger = Debugger::new(Computer::new(vec![1105, 1, -5]));
+        assert_eq!(run(&mut debugger, "s"), "    -5  not an address\n");
+        assert!(debugger.line_at(-5).is_none());
+        assert!(run(&mut debugger, "s").starts_with("error: "));
+    }
+}
@@ -0,0 +1,666 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::cfg::{self, BasicBlock, Exit};
+use crate::disasm::DisasmLine;
+use crate::{OpCode, ParamModes};
+
+// Turns a program into C-like pseudo-code, one function per subroutine the control flow graph
+// found. Relies on the idioms the puzzle programs are compiled with:
+//
+//   - a call stores the return address in rb+0, the arguments in rb+1 onwards and jumps
+//   - a subroutine starts with arb #frame, so rb-frame is where the return address went and the
+//     frame slots after it hold the arguments and locals, and returns with arb #-frame, jt #1, rb+0
+//   - jt #1 and jf #0 are unconditional jumps
+//
+// Frame slots read before the function writes them are shown as arguments, the rest as locals,
+// rb+1 onwards as out1, out2, ... and memory as mem[address]. main has no frame and moves rb
+// around as it likes, so there relative operands stay rb[offset]. Control flow that does not
+// fit loop, while and if is left as goto.
+pub fn decompile(program: &[i128]) -> String {
+    let cfg = cfg::build(program);
+    let mut text = String::new();
+
+    // How often each memory cell is read by the code, to tell comparison results nothing else uses
+    let mut reads = BTreeMap::new();
+    for line in cfg.blocks.values().flat_map(|block| block.lines.iter()) {
+        for (mode, value) in inputs(line) {
+            if mode == ParamModes::PositionMode {
+                *reads.entry(value).or_insert(0) += 1;
+            }
+        }
+    }
+
+    for (&entry, starts) in cfg.functions.iter() {
+        let blocks: Vec<&BasicBlock> = starts.iter().map(|start| &cfg.blocks[start]).collect();
+        text.push_str(&Function::new(entry, &blocks, &reads).decompile(&blocks));
+        text.push('\n');
+    }
+
+    text
+}
+
+enum Line {
+    // Only printed if something jumps to it with a goto
+    Label(usize),
+    Text(String)
+}
+
+// A comparison, or a value tested against zero
+#[derive(Clone)]
+struct Condition {
+    left: String,
+    op: &'static str,
+    right: String
+}
+
+impl Condition {
+    fn negate(&self) -> Condition {
+        let op = match self.op {
+            "<" => ">=",
+            ">=" => "<",
+            "==" => "!=",
+            _ => "=="
+        };
+        Condition { left: self.left.clone(), op, right: self.right.clone() }
+    }
+
+    fn render(&self) -> String {
+        match (self.op, self.right.as_str()) {
+            ("!=", "0") => self.left.clone(),
+            ("==", "0") => format!("!{}", self.left),
+            _ => format!("{} {} {}", self.left, self.op, self.right)
+        }
+    }
+}
+
+struct Function {
+    entry: usize,
+    frame: i128,
+    args: BTreeSet<i128>,
+    // Reads of memory cells by the whole program and of rb offsets by this function
+    memory_reads: BTreeMap<i128, usize>,
+    relative_reads: BTreeMap<i128, usize>,
+    lines: Vec<(usize, Line)>,
+    gotos: BTreeSet<usize>,
+    // Innermost last: the loop header and the address the loop exits to
+    loops: Vec<(usize, Option<usize>)>
+}
+
+impl Function {
+    fn new(entry: usize, blocks: &[&BasicBlock], memory_reads: &BTreeMap<i128, usize>) -> Function {
+        let frame = blocks.iter()
+            .find(|block| block.start == entry)
+            .and_then(|block| block.lines.first())
+            .filter(|line| is_op(line, OpCode::SetRelOffset) && mode(line, 0) == ParamModes::ImmediateMode)
+            .map(|line| line.words[1])
+            .filter(|&frame| frame > 0 && entry != 0)
+            .unwrap_or(0);
+
+        // Frame slots in address order, which is close enough to execution order to tell arguments.
+        // The jump a return ends with comes after the frame is gone, so it does not count.
+        let mut args = BTreeSet::new();
+        let mut written = BTreeSet::new();
+        let lines = blocks.iter().flat_map(|block| {
+            let returns = block.exit == Exit::Return;
+            block.lines.iter().take(block.lines.len() - returns as usize)
+        });
+        for line in lines {
+            let instruction = match &line.instruction {
+                Some(instruction) => instruction,
+                None => continue
+            };
+            for (index, &param_mode) in instruction.param_modes.iter().enumerate() {
+                let slot = line.words[index + 1] + frame;
+                if param_mode != ParamModes::RelativeMode || slot < 1 || slot > frame {
+                    continue;
+                }
+                if instruction.op_code.write_parameter() == Some(index) {
+                    written.insert(slot);
+                } else if !written.contains(&slot) {
+                    args.insert(slot);
+                }
+            }
+        }
+
+        let mut relative_reads = BTreeMap::new();
+        for line in blocks.iter().flat_map(|block| block.lines.iter()) {
+            for (mode, value) in inputs(line) {
+                if mode == ParamModes::RelativeMode {
+                    *relative_reads.entry(value).or_insert(0) += 1;
+                }
+            }
+        }
+
+        Function {
+            entry,
+            frame,
+            args,
+            memory_reads: memory_reads.clone(),
+            relative_reads,
+            lines: Vec::new(),
+            gotos: BTreeSet::new(),
+            loops: Vec::new()
+        }
+    }
+
+    fn decompile(mut self, blocks: &[&BasicBlock]) -> String {
+        // Blocks are laid out in address order, which need not start with the entry
+        if blocks.first().is_some_and(|block| block.start != self.entry) {
+            let jump = self.jump_to(self.entry);
+            self.push(1, jump);
+        }
+        self.emit_range(blocks, None, 1);
+
+        let mut text = if self.entry == 0 {
+            "fn main() {\n".to_string()
+        } else {
+            let args: Vec<String> = self.args.iter().map(|slot| format!("arg{}", slot)).collect();
+            format!("fn fn_{}({}) {{  // frame {}\n", self.entry, args.join(", "), self.frame)
+        };
+
+        for (depth, line) in self.lines.iter() {
+            match line {
+                Line::Label(address) if self.gotos.contains(address) => {
+                    text.push_str(&format!("{}L_{}:\n", "    ".repeat(depth - 1), address));
+                },
+                Line::Label(_) => {},
+                Line::Text(line) => text.push_str(&format!("{}{}\n", "    ".repeat(*depth), line))
+            }
+        }
+
+        text.push_str("}\n");
+        text
+    }
+
+    fn push(&mut self, depth: usize, line: String) {
+        self.lines.push((depth, Line::Text(line)));
+    }
+
+    // Emits blocks in order. follow is where control goes after the last of them.
+    fn emit_range(&mut self, blocks: &[&BasicBlock], follow: Option<usize>, depth: usize) {
+        let mut i = 0;
+
+        while i < blocks.len() {
+            let block = blocks[i];
+            let next = blocks.get(i + 1).map(|block| block.start).or(follow);
+
+            // A later block jumping back here makes this the head of a loop
+            if !self.loops.iter().any(|&(header, _)| header == block.start) {
+                let back_edge = (i..blocks.len()).rev().find(|&j| {
+                    !matches!(blocks[j].exit, Exit::Call { .. }) && blocks[j].exit.successors().contains(&block.start)
+                });
+                if let Some(j) = back_edge {
+                    let exit = blocks.get(j + 1).map(|block| block.start).or(follow);
+                    self.emit_loop(&blocks[i..=j], exit, depth);
+                    i = j + 1;
+                    continue;
+                }
+            }
+
+            self.lines.push((depth, Line::Label(block.start)));
+            let (statements, condition) = self.statements(block);
+            for statement in statements {
+                self.push(depth, statement);
+            }
+
+            match block.exit {
+                Exit::FallThrough(to) | Exit::Jump(to) => self.transfer(depth, to, next),
+                Exit::Branch { taken, not_taken } => {
+                    let condition = condition.expect("branches have a condition");
+
+                    // Skipping forward over some blocks, maybe with an else part after them
+                    let position = |address: usize| {
+                        blocks.iter().position(|block| block.start == address)
+                            .or(if Some(address) == follow { Some(blocks.len()) } else { None })
+                    };
+                    let structured = Some(not_taken) == next && self.structured_transfer(taken).is_none();
+                    match position(taken).filter(|&t| structured && t > i) {
+                        Some(t) => {
+                            let else_end = blocks[t - 1].exit.successors().first().copied()
+                                .filter(|_| matches!(blocks[t - 1].exit, Exit::Jump(_)))
+                                .and_then(position)
+                                .filter(|&e| e > t && t > i + 1);
+
+                            let if_line = self.lines.len();
+                            self.push(depth, format!("if ({}) {{", condition.negate().render()));
+                            match else_end {
+                                Some(e) => {
+                                    let end = blocks.get(e).map(|block| block.start).or(follow);
+                                    self.emit_range(&blocks[i + 1..t], end, depth + 1);
+                                    // Nothing to do in the then part, so only the else part is left
+                                    if self.lines[if_line + 1..].iter().all(|(_, line)| matches!(line, Line::Label(_))) {
+                                        self.lines[if_line].1 = Line::Text(format!("if ({}) {{", condition.render()));
+                                    } else {
+                                        self.push(depth, "} else {".to_string());
+                                    }
+                                    self.emit_range(&blocks[t..e], end, depth + 1);
+                                    self.push(depth, "}".to_string());
+                                    i = e;
+                                },
+                                None => {
+                                    self.emit_range(&blocks[i + 1..t], Some(taken), depth + 1);
+                                    self.push(depth, "}".to_string());
+                                    i = t;
+                                }
+                            }
+                            continue;
+                        },
+                        None => self.branch(depth, &condition, taken, not_taken, next)
+                    }
+                },
+                Exit::Call { return_to, .. } => self.transfer(depth, return_to, next),
+                Exit::Return => self.push(depth, "return;".to_string()),
+                Exit::Indirect { not_taken } => {
+                    let last = block.lines.last().expect("indirect jumps are an instruction");
+                    let target = self.operand(mode(last, 1), last.words[2]);
+                    match (condition, not_taken) {
+                        (Some(condition), Some(not_taken)) => {
+                            self.push(depth, format!("if ({}) goto *{};", condition.render(), target));
+                            self.transfer(depth, not_taken, next);
+                        },
+                        _ => self.push(depth, format!("goto *{};", target))
+                    }
+                },
+                Exit::Halt => self.push(depth, "halt;".to_string()),
+                Exit::Invalid => {
+                    let text = match block.lines.last().filter(|line| line.instruction.is_none()) {
+                        Some(line) => format!("// not code at {}: {}", line.address, line.text),
+                        None => "// runs off the end of the program".to_string()
+                    };
+                    self.push(depth, text);
+                }
+            }
+
+            i += 1;
+        }
+    }
+
+    fn emit_loop(&mut self, body: &[&BasicBlock], exit: Option<usize>, depth: usize) {
+        let header = body[0];
+        self.lines.push((depth, Line::Label(header.start)));
+        self.loops.push((header.start, exit));
+
+        // A header that only tests and leaves the loop reads better as a while
+        let (statements, condition) = self.statements(header);
+        match (header.exit, condition) {
+            (Exit::Branch { taken, not_taken }, Some(condition))
+                    if statements.is_empty() && Some(taken) == exit && body.len() > 1 && not_taken == body[1].start => {
+                self.push(depth, format!("while ({}) {{", condition.negate().render()));
+                self.emit_range(&body[1..], Some(header.start), depth + 1);
+            },
+            _ => {
+                self.push(depth, "loop {".to_string());
+                self.emit_range(body, Some(header.start), depth + 1);
+            }
+        }
+
+        self.push(depth, "}".to_string());
+        self.loops.pop();
+    }
+
+    fn branch(&mut self, depth: usize, condition: &Condition, taken: usize, not_taken: usize, next: Option<usize>) {
+        if Some(taken) == next {
+            let jump = self.jump_to(not_taken);
+            self.push(depth, format!("if ({}) {}", condition.negate().render(), jump));
+        } else {
+            let jump = self.jump_to(taken);
+            self.push(depth, format!("if ({}) {}", condition.render(), jump));
+            self.transfer(depth, not_taken, next);
+        }
+    }
+
+    // Goes to address unless that is where control ends up anyway
+    fn transfer(&mut self, depth: usize, address: usize, next: Option<usize>) {
+        if Some(address) != next {
+            let jump = self.jump_to(address);
+            self.push(depth, jump);
+        }
+    }
+
+    fn structured_transfer(&self, address: usize) -> Option<&'static str> {
+        match self.loops.last() {
+            Some(&(header, _)) if header == address => Some("continue;"),
+            Some(&(_, Some(exit))) if exit == address => Some("break;"),
+            _ => None
+        }
+    }
+
+    fn jump_to(&mut self, address: usize) -> String {
+        match self.structured_transfer(address) {
+            Some(jump) => jump.to_string(),
+            None => {
+                self.gotos.insert(address);
+                format!("goto L_{};", address)
+            }
+        }
+    }
+
+    // The block's instructions as statements, leaving out the jump it ends with. For branches the
+    // condition comes back separately, with a comparison right before it folded in.
+    fn statements(&self, block: &BasicBlock) -> (Vec<String>, Option<Condition>) {
+        let jumps = matches!(block.exit, Exit::Jump(_) | Exit::Branch { .. } | Exit::Call { .. } | Exit::Return | Exit::Indirect { .. });
+        let mut lines: Vec<&DisasmLine> = block.lines.iter().collect();
+        let last = if jumps { lines.pop() } else { None };
+        if block.exit == Exit::Halt || (block.exit == Exit::Invalid && lines.last().is_some_and(|line| line.instruction.is_none())) {
+            lines.pop();
+        }
+
+        // Frame setup and teardown are implied by fn and return
+        if block.start == self.entry && self.frame != 0 {
+            lines.retain(|line| !(line.address == self.entry && is_op(line, OpCode::SetRelOffset)));
+        }
+        if block.exit == Exit::Return && lines.last().is_some_and(|line| is_op(line, OpCode::SetRelOffset)) {
+            lines.pop();
+        }
+
+        let mut condition = None;
+        if let Some(last) = last.filter(|line| is_op(line, OpCode::JIfTrue) || is_op(line, OpCode::JIfFalse)) {
+            if mode(last, 0) != ParamModes::ImmediateMode {
+                let tested = (mode(last, 0), last.words[1]);
+                // Only if the jump is the one place the result is read, otherwise it has to be kept
+                let folded = lines.last()
+                    .filter(|line| is_op(line, OpCode::Lt) || is_op(line, OpCode::Eq))
+                    .filter(|line| (mode(line, 2), line.words[3]) == tested)
+                    .filter(|_| self.read_count(tested.0, tested.1) == 1)
+                    .map(|line| Condition {
+                        left: self.operand(mode(line, 0), line.words[1]),
+                        op: if is_op(line, OpCode::Lt) { "<" } else { "==" },
+                        right: self.operand(mode(line, 1), line.words[2])
+                    });
+                if folded.is_some() {
+                    lines.pop();
+                }
+
+                let tested = folded.unwrap_or_else(|| Condition {
+                    left: self.operand(tested.0, tested.1),
+                    op: "!=",
+                    right: "0".to_string()
+                });
+                condition = Some(if is_op(last, OpCode::JIfTrue) { tested } else { tested.negate() });
+            }
+        }
+
+        // In a call block the arguments are kept here until the call. A slot written again, or read
+        // by another statement before the call, stands for the value it was given.
+        let mut statements = Vec::new();
+        let mut call_args = BTreeMap::new();
+        for line in lines {
+            if let Exit::Call { .. } = block.exit {
+                // The return address and the arguments become part of the call
+                if cfg::stored_return(line).is_some() {
+                    continue;
+                }
+                if let Some((slot, value)) = self.out_argument(line, &call_args) {
+                    call_args.insert(slot, value);
+                    continue;
+                }
+            }
+            statements.push(self.statement(line, &call_args));
+
+            // Anything else writing a slot, like an input, sets it directly
+            if let Some(index) = line.instruction.as_ref().and_then(|instruction| instruction.op_code.write_parameter()) {
+                if mode(line, index) == ParamModes::RelativeMode {
+                    call_args.remove(&line.words[index + 1]);
+                }
+            }
+        }
+
+        if let Exit::Call { target, .. } = block.exit {
+            let count = call_args.keys().last().copied().unwrap_or(0);
+            let args: Vec<String> = (1..=count)
+                .map(|slot| call_args.remove(&slot).unwrap_or_else(|| format!("out{}", slot)))
+                .collect();
+            statements.push(format!("fn_{}({});", target, args.join(", ")));
+        }
+
+        (statements, condition)
+    }
+
+    fn statement(&self, line: &DisasmLine, call_args: &BTreeMap<i128, String>) -> String {
+        let instruction = match &line.instruction {
+            Some(instruction) => instruction,
+            None => return format!("// {}", line.text)
+        };
+        let operand = |index: usize| self.operand(instruction.param_modes[index], line.words[index + 1]);
+
+        match instruction.op_code {
+            OpCode::Add | OpCode::Multiply | OpCode::Lt | OpCode::Eq => {
+                format!("{} = {};", operand(2), self.expression(line, call_args))
+            },
+            OpCode::PrintAddress => format!("output({});", self.argument(mode(line, 0), line.words[1], call_args)),
+            OpCode::ReadInput => format!("{} = input();", operand(0)),
+            OpCode::SetRelOffset => format!("rb += {};", operand(0)),
+            _ => format!("// {}", line.text)
+        }
+    }
+
+    // Right hand side of add, mul, lt and eq, with adding zero and multiplying by one dropped
+    fn expression(&self, line: &DisasmLine, call_args: &BTreeMap<i128, String>) -> String {
+        let immediate = |index: usize| {
+            if mode(line, index) == ParamModes::ImmediateMode { Some(line.words[index + 1]) } else { None }
+        };
+        let left = self.argument(mode(line, 0), line.words[1], call_args);
+        let right = self.argument(mode(line, 1), line.words[2], call_args);
+
+        match (&line.instruction.as_ref().expect("only called on instructions").op_code, immediate(0), immediate(1)) {
+            (OpCode::Add, Some(0), _) => right,
+            (OpCode::Add, _, Some(0)) => left,
+            (OpCode::Add, _, Some(value)) if value < 0 => format!("{} - {}", left, -value),
+            (OpCode::Add, Some(value), _) if value < 0 => format!("{} - {}", right, -value),
+            (OpCode::Add, _, _) => format!("{} + {}", left, right),
+            (OpCode::Multiply, Some(1), _) => right,
+            (OpCode::Multiply, _, Some(1)) => left,
+            (OpCode::Multiply, Some(-1), _) => format!("-{}", right),
+            (OpCode::Multiply, _, Some(-1)) => format!("-{}", left),
+            (OpCode::Multiply, _, _) => format!("{} * {}", left, right),
+            (OpCode::Lt, _, _) => format!("{} < {}", left, right),
+            _ => format!("{} == {}", left, right)
+        }
+    }
+
+    // A write of a call argument, as the slot it goes to and the value
+    fn out_argument(&self, line: &DisasmLine, call_args: &BTreeMap<i128, String>) -> Option<(i128, String)> {
+        let instruction = line.instruction.as_ref()?;
+        let index = instruction.op_code.write_parameter()?;
+        if instruction.op_code == OpCode::ReadInput
+            || instruction.param_modes[index] != ParamModes::RelativeMode || line.words[index + 1] < 1 {
+            return None;
+        }
+
+        Some((line.words[index + 1], self.expression(line, call_args)))
+    }
+
+    // An operand read in a call block, where out slots already given a value stand for it
+    fn argument(&self, param_mode: ParamModes, value: i128, call_args: &BTreeMap<i128, String>) -> String {
+        match call_args.get(&value).filter(|_| param_mode == ParamModes::RelativeMode) {
+            Some(argument) if argument.contains(' ') => format!("({})", argument),
+            Some(argument) => argument.clone(),
+            None => self.operand(param_mode, value)
+        }
+    }
+
+    fn read_count(&self, param_mode: ParamModes, value: i128) -> usize {
+        let reads = match param_mode {
+            ParamModes::PositionMode => &self.memory_reads,
+            _ => &self.relative_reads
+        };
+        reads.get(&value).copied().unwrap_or(0)
+    }
+
+    fn operand(&self, param_mode: ParamModes, value: i128) -> String {
+        match param_mode {
+            ParamModes::ImmediateMode => value.to_string(),
+            ParamModes::PositionMode => format!("mem[{}]", value),
+            ParamModes::RelativeMode => {
+                let slot = value + self.frame;
+                if self.entry == 0 || slot < 0 {
+                    format!("rb[{}]", value)
+                } else if value >= 1 {
+                    format!("out{}", value)
+                } else if slot == 0 {
+                    "return_address".to_string()
+                } else if self.args.contains(&slot) {
+                    format!("arg{}", slot)
+                } else {
+                    format!("local{}", slot)
+                }
+            }
+        }
+    }
+}
+
+fn is_op(line: &DisasmLine, op_code: OpCode) -> bool {
+    line.instruction.as_ref().is_some_and(|instruction| instruction.op_code == op_code)
+}
+
+// The operands an instruction reads, leaving out immediates and the one it writes
+fn inputs(line: &DisasmLine) -> Vec<(ParamModes, i128)> {
+    let instruction = match &line.instruction {
+        Some(instruction) => instruction,
+        None => return Vec::new()
+    };
+
+    instruction.param_modes.iter()
+        .enumerate()
+        .filter(|&(index, &param_mode)| {
+            param_mode != ParamModes::ImmediateMode && instruction.op_code.write_parameter() != Some(index)
+        })
+        .map(|(index, &param_mode)| (param_mode, line.words[index + 1]))
+        .collect()
+}
+
+fn mode(line: &DisasmLine, index: usize) -> ParamModes {
+    line.instruction.as_ref().map(|instruction| instruction.param_modes[index]).unwrap_or(ParamModes::ImmediateMode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    fn decompiled(source: &str) -> String {
+        decompile(&asm::assemble(source).unwrap())
+    }
+
+    const IF_ELSE: &str = "
+                in [x]
+                lt [x], #10, [small]
+                jf [small], #big
+                out #1
+                jt #1, #done
+        big:    out #2
+        done:   hlt
+        x:      .data 0
+        small:  .data 0
+    ";
+
+    const LOOP: &str = "
+                in [n]
+        again:  out [n]
+                add [n], #-1, [n]
+                jt [n], #again
+                hlt
+        n:      .data 0
+    ";
+
+    // The call block of fn_601 in day 13, where the first argument slot is written twice
+    const CALL: &str = "
+                arb #100
+                in rb+1
+                in rb+2
+                add #0, #done, rb+0
+                jt #1, #func
+        done:   out rb+1
+                hlt
+        func:   arb #3
+                mul #20, rb-2, rb+1
+                add rb+1, rb-1, rb+1
+                mul #1, #383, rb+2
+                add #0, #back, rb+0
+                jt #1, #callee
+        back:   add rb+1, #1399, rb-2
+                arb #-3
+                jf #0, rb+0
+        callee: out rb+1
+                out rb+2
+                jf #0, rb+0
+    ";
+
+    const KEPT_COMPARISON: &str = "
+                in [x]
+                eq [x], #5, [flag]
+                jt [flag], #five
+                out [flag]
+        five:   hlt
+        x:      .data 0
+        flag:   .data 0
+    ";
+
+    // The function jumps back to a block below its entry
+    const ENTRY_LAST: &str = "
+                arb #100
+                in rb+1
+                add #0, #done, rb+0
+                jt #1, #func
+        done:   hlt
+        tail:   out rb-1
+                arb #-2
+                jf #0, rb+0
+        func:   arb #2
+                jt #1, #tail
+    ";
+
+    #[test]
+    fn if_else() {
+        assert_eq!(decompiled(IF_ELSE), "\
+fn main() {
+    mem[17] = input();
+    if (mem[17] < 10) {
+        output(1);
+    } else {
+        output(2);
+    }
+    halt;
+}
+
+");
+    }
+
+    #[test]
+    fn backward_jump_is_a_loop() {
+        assert_eq!(decompiled(LOOP), "\
+fn main() {
+    mem[12] = input();
+    loop {
+        output(mem[12]);
+        mem[12] = mem[12] - 1;
+        if (!mem[12]) break;
+    }
+    halt;
+}
+
+");
+    }
+
+    #[test]
+    fn call_arguments_are_folded_into_the_call() {
+        let text = decompiled(CALL);
+        assert!(text.contains("    fn_16();\n"), "{}", text);
+        assert!(text.contains("fn fn_16(arg1, arg2) {  // frame 3\n"), "{}", text);
+        // The second write to out1 reads the first, which has to end up inside it
+        assert!(text.contains("    fn_46((20 * arg1) + arg2, 383);\n    arg1 = out1 + 1399;\n"), "{}", text);
+    }
+
+    #[test]
+    fn comparison_read_again_is_kept() {
+        let text = decompiled(KEPT_COMPARISON);
+        assert!(text.contains("    mem[13] = mem[12] == 5;\n    if (!mem[13]) {\n        output(mem[13]);\n"), "{}", text);
+    }
+
+    #[test]
+    fn frame_comes_from_the_entry_block() {
+        let text = decompiled(ENTRY_LAST);
+        assert!(text.contains("fn fn_19(arg1) {  // frame 2\n    goto L_19;\n"), "{}", text);
+        assert!(text.contains("output(arg1);"), "{}", text);
+    }
+}
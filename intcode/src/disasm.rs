@@ -0,0 +1,143 @@
+use std::fmt;
+
+use crate::{Computer, Instruction, ParamModes};
+
+// One line of the listing: either a decoded instruction or a single .data word
+#[derive(PartialEq, Debug, Clone)]
+pub struct DisasmLine {
+    pub address: usize,
+    pub words: Vec<i128>,
+    pub instruction: Option<Instruction>,
+    pub text: String
+}
+
+impl DisasmLine {
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+impl fmt::Display for DisasmLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let raw: Vec<String> = self.words.iter().map(|w| w.to_string()).collect();
+        write!(f, "{:>6}  {:<28} {}", self.address, raw.join(","), self.text)
+    }
+}
+
+// Linear sweep over the whole program. Words that do not decode, or instructions that would run
+// past the end of the program, are listed as .data.
+pub fn disassemble(program: &[i128]) -> Vec<DisasmLine> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+
+    while let Some(line) = decode_at(program, address) {
+        address += line.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+// None past the end of the program
+pub fn decode_at(program: &[i128], address: usize) -> Option<DisasmLine> {
+    decode_words(address, program.get(address..)?)
+}
+
+// Decodes the instruction starting at words[0], which lives at address. None if there are no words.
+pub fn decode_words(address: usize, words: &[i128]) -> Option<DisasmLine> {
+    let code = *words.first()?;
+
+    if let Ok(instruction) = Computer::get_instruction(address as i128, code) {
+        let param_count = Computer::get_number_parameters(&instruction.op_code) as usize;
+        // Writing to an immediate fails when run, and the assembler does not take it either
+        let writes_immediate = instruction.op_code.write_parameter()
+            .is_some_and(|idx| instruction.param_modes[idx] == ParamModes::ImmediateMode);
+
+        if param_count < words.len() && !writes_immediate {
+            let mut text = format_instruction(&instruction, &words[1..=param_count]);
+
+            // Extra mode digits still run, but the mnemonic alone would assemble to another word.
+            // The words are kept as data so the listing stays true to the program.
+            if instruction.code() != code {
+                let raw: Vec<String> = words[..=param_count].iter().map(|w| w.to_string()).collect();
+                text = format!(".data {}  ; {}", raw.join(", "), text);
+            }
+
+            return Some(DisasmLine {
+                address,
+                words: words[..=param_count].to_vec(),
+                instruction: Some(instruction),
+                text
+            });
+        }
+    }
+
+    Some(DisasmLine {
+        address,
+        words: vec![code],
+        instruction: None,
+        text: format!(".data {}", code)
+    })
+}
+
+pub fn format_instruction(instruction: &Instruction, params: &[i128]) -> String {
+    let operands: Vec<String> = instruction.param_modes.iter()
+        .zip(params)
+        .map(|(mode, &value)| format_operand(mode, value))
+        .collect();
+
+    if operands.is_empty() {
+        instruction.op_code.mnemonic().to_string()
+    } else {
+        format!("{} {}", instruction.op_code.mnemonic(), operands.join(", "))
+    }
+}
+
+pub fn format_operand(mode: &ParamModes, value: i128) -> String {
+    match mode {
+        ParamModes::PositionMode => format!("[{}]", value),
+        ParamModes::ImmediateMode => format!("#{}", value),
+        ParamModes::RelativeMode if value < 0 => format!("rb-{}", -value),
+        ParamModes::RelativeMode => format!("rb+{}", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extra_mode_digits_are_listed_as_data() {
+        let line = decode_words(20, &[99999]).unwrap();
+        assert_eq!(line.text, ".data 99999  ; hlt");
+        assert!(line.instruction.is_some());
+
+        let line = decode_words(0, &[121101, 1, 2, 3]).unwrap();
+        assert_eq!(line.text, ".data 121101, 1, 2, 3  ; add #1, #2, rb+3");
+        assert_eq!(line.len(), 4);
+    }
+
+    #[test]
+    fn canonical_codes_decode_as_instructions() {
+        assert_eq!(decode_words(0, &[1002, 4, 3, 4]).unwrap().text, "mul [4], #3, [4]");
+        assert_eq!(decode_words(0, &[99]).unwrap().text, "hlt");
+    }
+
+    #[test]
+    fn immediate_write_is_listed_as_data() {
+        assert_eq!(decode_words(369, &[103, 111]).unwrap().text, ".data 103");
+        assert_eq!(decode_words(0, &[1101, 1, 2, 3]).unwrap().text, "add #1, #2, [3]");
+    }
+
+    #[test]
+    fn nothing_to_decode() {
+        assert_eq!(decode_words(0, &[]), None);
+        assert_eq!(decode_at(&[1, 0, 0, 0, 99], 5), None);
+        assert_eq!(decode_at(&[1, 0, 0, 0, 99], 6), None);
+        assert_eq!(disassemble(&[]), Vec::new());
+    }
+}
@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::{Limit, OpCode};
+
+// Every error carries the address of the instruction that was executing when it happened
+#[derive(PartialEq, Debug, Clone)]
+pub enum IntcodeError {
+    InvalidOpCode { address: i128, code: i128 },
+    InvalidParamMode { address: i128, mode: i128 },
+    WriteInImmediateMode { address: i128 },
+    NegativeAddress { address: i128, position: i128 },
+    InputExhausted { address: i128 },
+    // Only from run_to_halt, other runs stop with RunState::BudgetExceeded
+    BudgetExceeded { address: i128, limit: Limit },
+    // A word used as an opcode, address or relative base offset does not fit in an i128
+    WordOutOfRange { address: i128 },
+    // Checked arithmetic only. Operands are the values added or multiplied.
+    Overflow { address: i128, op_code: OpCode, left: i128, right: i128 }
+}
+
+impl IntcodeError {
+    pub fn address(&self) -> i128 {
+        match *self {
+            IntcodeError::InvalidOpCode { address, .. } => address,
+            IntcodeError::InvalidParamMode { address, .. } => address,
+            IntcodeError::WriteInImmediateMode { address } => address,
+            IntcodeError::NegativeAddress { address, .. } => address,
+            IntcodeError::InputExhausted { address } => address,
+            IntcodeError::BudgetExceeded { address, .. } => address,
+            IntcodeError::WordOutOfRange { address } => address,
+            IntcodeError::Overflow { address, .. } => address
+        }
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntcodeError::InvalidOpCode { address, code } => {
+                write!(f, "invalid opcode {} at address {}", code, address)
+            },
+            IntcodeError::InvalidParamMode { address, mode } => {
+                write!(f, "invalid parameter mode {} at address {}", mode, address)
+            },
+            IntcodeError::WriteInImmediateMode { address } => {
+                write!(f, "write parameter in immediate mode at address {}", address)
+            },
+            IntcodeError::NegativeAddress { address, position } => {
+                write!(f, "access to negative position {} at address {}", position, address)
+            },
+            IntcodeError::InputExhausted { address } => {
+                write!(f, "input exhausted at address {}", address)
+            },
+            IntcodeError::BudgetExceeded { address, limit } => {
+                write!(f, "{:?} budget exceeded at address {}", limit, address)
+            },
+            IntcodeError::WordOutOfRange { address } => {
+                write!(f, "value too large to use as an opcode or address at address {}", address)
+            },
+            IntcodeError::Overflow { address, op_code, left, right } => {
+                write!(f, "{} {}, {} overflows at address {}", op_code.mnemonic(), left, right, address)
+            }
+        }
+    }
+}
+
+impl Error for IntcodeError {}
@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+
+use crate::Word;
+
+// What it takes to undo one executed instruction
+#[derive(PartialEq, Debug, Clone)]
+pub(crate) struct UndoRecord<W> {
+    pub(crate) instruction_pointer: i128,
+    pub(crate) relative_base: i128,
+    // Cells in the order they were written, with what they held before
+    pub(crate) writes: Vec<(i128, W)>,
+    // Set when the instruction took a value from the computer's own input queue
+    pub(crate) input: Option<W>,
+    // Set when the instruction left a value in the computer's own output queue
+    pub(crate) queued_output: Option<W>
+}
+
+impl<W: Word> UndoRecord<W> {
+    pub(crate) fn new(instruction_pointer: i128, relative_base: i128) -> UndoRecord<W> {
+        UndoRecord {
+            instruction_pointer,
+            relative_base,
+            writes: Vec::new(),
+            input: None,
+            queued_output: None
+        }
+    }
+}
+
+// The last `limit` instructions, oldest first
+pub(crate) struct History<W> {
+    pub(crate) records: VecDeque<UndoRecord<W>>,
+    pub(crate) limit: usize,
+    pub(crate) pending: Option<UndoRecord<W>>
+}
+
+impl<W: Word> History<W> {
+    pub(crate) fn new(limit: usize) -> History<W> {
+        History {
+            records: VecDeque::new(),
+            limit,
+            pending: None
+        }
+    }
+
+    pub(crate) fn push(&mut self, record: UndoRecord<W>) {
+        if self.limit == 0 {
+            return;
+        }
+
+        if self.records.len() == self.limit {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+// What the computer talks to when running ReadInput and PrintAddress. Returning None from
+// read_input pauses the machine on the read instruction so it can be resumed later.
+pub trait IntcodeIo<W = i128> {
+    fn read_input(&mut self) -> Option<W>;
+    fn write_output(&mut self, value: W);
+}
+
+#[derive(Debug, Clone)]
+pub struct QueueIo<W = i128> {
+    pub input: VecDeque<W>,
+    pub output: VecDeque<W>
+}
+
+impl<W> QueueIo<W> {
+    pub fn new(input: VecDeque<W>) -> QueueIo<W> {
+        QueueIo {
+            input,
+            output: VecDeque::new()
+        }
+    }
+}
+
+// Not derived so that W does not need a Default of its own
+impl<W> Default for QueueIo<W> {
+    fn default() -> QueueIo<W> {
+        QueueIo::new(VecDeque::new())
+    }
+}
+
+impl<W> IntcodeIo<W> for QueueIo<W> {
+    fn read_input(&mut self) -> Option<W> {
+        self.input.pop_front()
+    }
+
+    fn write_output(&mut self, value: W) {
+        self.output.push_back(value);
+    }
+}
+
+// Feeds input from any iterator and collects everything printed
+pub struct IterIo<I: Iterator> {
+    input: I,
+    pub output: Vec<I::Item>
+}
+
+impl<I: Iterator> IterIo<I> {
+    pub fn new<T: IntoIterator<Item = I::Item, IntoIter = I>>(input: T) -> IterIo<I> {
+        IterIo {
+            input: input.into_iter(),
+            output: Vec::new()
+        }
+    }
+}
+
+impl<I: Iterator> IntcodeIo<I::Item> for IterIo<I> {
+    fn read_input(&mut self) -> Option<I::Item> {
+        self.input.next()
+    }
+
+    fn write_output(&mut self, value: I::Item) {
+        self.output.push(value);
+    }
+}
+
+pub struct FnIo<R, F> {
+    read: R,
+    write: F
+}
+
+impl<R, F> FnIo<R, F> {
+    pub fn new(read: R, write: F) -> FnIo<R, F> {
+        FnIo {
+            read,
+            write
+        }
+    }
+}
+
+impl<W, R: FnMut() -> Option<W>, F: FnMut(W)> IntcodeIo<W> for FnIo<R, F> {
+    fn read_input(&mut self) -> Option<W> {
+        (self.read)()
+    }
+
+    fn write_output(&mut self, value: W) {
+        (self.write)(value)
+    }
+}
+
+// Blocks on the receiver for input. Once every sender is gone the machine pauses waiting for input.
+// Outputs sent after the receiving side hung up are dropped.
+pub struct ChannelIo<W = i128> {
+    input: Receiver<W>,
+    output: Sender<W>
+}
+
+impl<W> ChannelIo<W> {
+    pub fn new(input: Receiver<W>, output: Sender<W>) -> ChannelIo<W> {
+        ChannelIo {
+            input,
+            output
+        }
+    }
+}
+
+impl<W> IntcodeIo<W> for ChannelIo<W> {
+    fn read_input(&mut self) -> Option<W> {
+        self.input.recv().ok()
+    }
+
+    fn write_output(&mut self, value: W) {
+        let _ = self.output.send(value);
+    }
+}
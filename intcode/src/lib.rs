@@ -0,0 +1,1167 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::mem;
+use std::num::ParseIntError;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+pub mod asm;
+mod budget;
+mod cache;
+pub mod cfg;
+mod cluster;
+mod coverage;
+pub mod debugger;
+pub mod decompile;
+pub mod disasm;
+mod error;
+mod history;
+mod io;
+mod memory;
+mod packet;
+mod profile;
+pub mod search;
+mod snapshot;
+pub mod solve;
+pub mod trace;
+mod watch;
+mod word;
+
+pub use budget::{Budget, Limit};
+pub use cluster::{Cluster, ClusterRun, Topology};
+pub use coverage::{CellUse, Coverage};
+pub use error::IntcodeError;
+pub use io::{ChannelIo, FnIo, IntcodeIo, IterIo, QueueIo};
+pub use memory::Memory;
+pub use packet::{IdleAction, NetworkMonitor, NetworkStop, Packet, PacketNetwork};
+pub use profile::Profile;
+pub use search::{Candidate, Outcome, Search};
+pub use snapshot::Snapshot;
+pub use trace::{TraceFormat, TraceRecord};
+use budget::BudgetTracker;
+use cache::{DecodeCache, Decoded};
+use history::{History, UndoRecord};
+use trace::Tracer;
+pub use watch::{Access, WatchAction, WatchCallback, WatchEvent, Watchpoint};
+pub use word::{Arithmetic, Word};
+
+// Parses the comma separated format the puzzle inputs come in
+pub fn parse_program(line: &str) -> Result<Vec<i128>, ParseIntError> {
+    line.trim().split(',').map(|x| x.trim().parse::<i128>()).collect()
+}
+
+// Same for any word type, e.g. parse_program_as::<BigInt>
+pub fn parse_program_as<W: Word>(line: &str) -> Result<Vec<W>, <W as FromStr>::Err> {
+    line.trim().split(',').map(|x| x.trim().parse::<W>()).collect()
+}
+
+// For the command line tools: a computer loaded with the program in the file, with the inputs
+// given after it on the command line queued up
+pub fn load_program_file(path: &str, inputs: &[String]) -> std::io::Result<Computer> {
+    let program = parse_program(&fs::read_to_string(path)?)
+        .map_err(|err| std::io::Error::new(ErrorKind::InvalidData, err))?;
+
+    let mut computer = Computer::new(program);
+    for input in inputs.iter() {
+        let value = input.parse::<i128>()
+            .map_err(|err| std::io::Error::new(ErrorKind::InvalidInput, err))?;
+        computer.push_input(value);
+    }
+
+    Ok(computer)
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+pub enum OpCode {
+    Add = 1,
+    Multiply,
+    ReadInput,
+    PrintAddress,
+    JIfTrue,
+    JIfFalse,
+    Lt,
+    Eq,
+    SetRelOffset,
+    Halt = 99
+}
+
+impl OpCode {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            OpCode::Add => "add",
+            OpCode::Multiply => "mul",
+            OpCode::ReadInput => "in",
+            OpCode::PrintAddress => "out",
+            OpCode::JIfTrue => "jt",
+            OpCode::JIfFalse => "jf",
+            OpCode::Lt => "lt",
+            OpCode::Eq => "eq",
+            OpCode::SetRelOffset => "arb",
+            OpCode::Halt => "hlt"
+        }
+    }
+
+    // Index of the parameter the instruction stores its result to, if any
+    pub fn write_parameter(&self) -> Option<usize> {
+        match self {
+            OpCode::Add | OpCode::Multiply | OpCode::Lt | OpCode::Eq => Some(2),
+            OpCode::ReadInput => Some(0),
+            _ => None
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<OpCode> {
+        match mnemonic {
+            "add" => Some(OpCode::Add),
+            "mul" => Some(OpCode::Multiply),
+            "in" => Some(OpCode::ReadInput),
+            "out" => Some(OpCode::PrintAddress),
+            "jt" => Some(OpCode::JIfTrue),
+            "jf" => Some(OpCode::JIfFalse),
+            "lt" => Some(OpCode::Lt),
+            "eq" => Some(OpCode::Eq),
+            "arb" => Some(OpCode::SetRelOffset),
+            "hlt" => Some(OpCode::Halt),
+            _ => None
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ParamModes {
+    PositionMode = 0,
+    ImmediateMode,
+    RelativeMode
+}
+
+#[derive(PartialEq, Debug)]
+pub enum RunState<W = i128> {
+    NeedsInput,
+    Output(W),
+    Halted,
+    // Stopped after an instruction that touched memory under a Pause watchpoint
+    Watchpoint(Vec<WatchEvent<W>>),
+    // Stopped before the next instruction. Runs keep stopping here until the budget is changed.
+    BudgetExceeded(Limit)
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Instruction {
+    pub op_code: OpCode,
+    pub param_modes: Vec<ParamModes>
+}
+
+impl Instruction {
+    // The word that encodes it, with no mode digits past the last parameter
+    pub fn code(&self) -> i128 {
+        let mut code = self.op_code as i128;
+        let mut factor = 100;
+        for mode in self.param_modes.iter() {
+            code += *mode as i128 * factor;
+            factor *= 10;
+        }
+
+        code
+    }
+}
+
+// Generic over what a memory cell holds. i128 unless asked otherwise, see Word for the choices.
+pub struct Computer<W: Word = i128> {
+    memory: Memory<W>,
+    decode_cache: DecodeCache,
+    relative_base: i128,
+    instruction_pointer: i128,
+    queues: QueueIo<W>,
+    watchpoints: Vec<Watchpoint<W>>,
+    next_watch_id: usize,
+    watch_hits: Vec<WatchEvent<W>>,
+    steps: u64,
+    arithmetic: Arithmetic,
+    budget: Option<BudgetTracker>,
+    profile: Option<Profile>,
+    coverage: Option<Coverage>,
+    tracer: Option<Tracer<W>>,
+    history: Option<History<W>>,
+    // True while executing against the computer's own queues rather than a device
+    on_own_queues: bool
+}
+
+impl<W: Word> Computer<W> {
+    pub fn new(init_memory: Vec<W>) -> Computer<W> {
+        Computer {
+            decode_cache: DecodeCache::new(init_memory.len()),
+            memory: Memory::new(init_memory),
+            relative_base: 0,
+            instruction_pointer: 0,
+            queues: QueueIo::default(),
+            watchpoints: Vec::new(),
+            next_watch_id: 0,
+            watch_hits: Vec::new(),
+            steps: 0,
+            arithmetic: Arithmetic::default(),
+            budget: None,
+            profile: None,
+            coverage: None,
+            tracer: None,
+            history: None,
+            on_own_queues: false
+        }
+    }
+
+    pub fn from_snapshot(snapshot: Snapshot<W>) -> Computer<W> {
+        let mut computer = Computer::new(Vec::new());
+        computer.decode_cache = DecodeCache::new(snapshot.memory.image_len());
+        computer.memory = snapshot.memory;
+        computer.instruction_pointer = snapshot.instruction_pointer;
+        computer.relative_base = snapshot.relative_base;
+        computer.steps = snapshot.steps;
+        computer.queues = QueueIo { input: snapshot.input, output: snapshot.output };
+
+        computer
+    }
+
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory: self.memory.clone(),
+            instruction_pointer: self.instruction_pointer,
+            relative_base: self.relative_base,
+            steps: self.steps,
+            input: self.queues.input.clone(),
+            output: self.queues.output.clone()
+        }
+    }
+
+    // Puts the machine back in the saved state. Watchpoints and any running trace are kept.
+    pub fn restore(&mut self, snapshot: &Snapshot<W>) {
+        self.memory = snapshot.memory.clone();
+        self.decode_cache = DecodeCache::new(self.memory.image_len());
+        self.instruction_pointer = snapshot.instruction_pointer;
+        self.relative_base = snapshot.relative_base;
+        self.steps = snapshot.steps;
+        self.queues = QueueIo { input: snapshot.input.clone(), output: snapshot.output.clone() };
+        self.watch_hits.clear();
+
+        // Undo records describe how we got to the old state, not to this one
+        if let Some(history) = &mut self.history {
+            history.records.clear();
+        }
+    }
+
+    // Runs until the program halts or asks for input that was not pushed yet. Outputs are kept
+    // in the output queue. Calling it again resumes from the same instruction.
+    pub fn run(&mut self) -> Result<RunState<W>, IntcodeError> {
+        self.execute_with_queues(false)
+    }
+
+    // Runs against a device instead of the computer's own queues. Resuming works the same way.
+    pub fn run_with<T: IntcodeIo<W> + ?Sized>(&mut self, io: &mut T) -> Result<RunState<W>, IntcodeError> {
+        self.execute(io, false)
+    }
+
+    // For programs that get all their input upfront: asking for more is an error instead of a pause,
+    // and so is running out of budget. Pause watchpoints are run past, callbacks still get called.
+    pub fn run_to_halt(&mut self) -> Result<(), IntcodeError> {
+        loop {
+            match self.run()? {
+                RunState::Halted => return Ok(()),
+                RunState::Watchpoint(_) => {},
+                RunState::BudgetExceeded(limit) => {
+                    return Err(IntcodeError::BudgetExceeded { address: self.instruction_pointer, limit });
+                },
+                _ => return Err(IntcodeError::InputExhausted { address: self.instruction_pointer })
+            }
+        }
+    }
+
+    // Same as run but also stops as soon as a value is printed, handing it back instead of queueing it.
+    pub fn run_until_output(&mut self) -> Result<RunState<W>, IntcodeError> {
+        self.execute_with_queues(true)
+    }
+
+    // Executes a single instruction against the computer's own queues. None means it can keep going.
+    pub fn step(&mut self) -> Result<Option<RunState<W>>, IntcodeError> {
+        let mut queues = mem::take(&mut self.queues);
+        self.on_own_queues = true;
+        let state = self.execute_watched(&mut queues, false);
+        self.on_own_queues = false;
+        self.queues = queues;
+
+        state
+    }
+
+    fn execute_with_queues(&mut self, stop_on_output: bool) -> Result<RunState<W>, IntcodeError> {
+        let mut queues = mem::take(&mut self.queues);
+        self.on_own_queues = true;
+        let state = self.execute(&mut queues, stop_on_output);
+        self.on_own_queues = false;
+        self.queues = queues;
+
+        state
+    }
+
+    fn execute<T: IntcodeIo<W> + ?Sized>(&mut self, io: &mut T, stop_on_output: bool) -> Result<RunState<W>, IntcodeError> {
+        loop {
+            if let Some(state) = self.execute_watched(io, stop_on_output)? {
+                return Ok(state);
+            }
+        }
+    }
+
+    // Watchpoint hits are reported once the instruction that caused them is done. If that instruction
+    // already stops the run (an output handed back, say) they are reported on the next call instead.
+    fn execute_watched<T: IntcodeIo<W> + ?Sized>(&mut self, io: &mut T, stop_on_output: bool) -> Result<Option<RunState<W>>, IntcodeError> {
+        if self.watch_hits.is_empty() {
+            if let Some(tracker) = &self.budget {
+                if let Some(limit) = tracker.check(self.steps, self.memory.allocated_cells()) {
+                    return Ok(Some(RunState::BudgetExceeded(limit)));
+                }
+            }
+
+            if let Some(state) = self.execute_instruction(io, stop_on_output)? {
+                return Ok(Some(state));
+            }
+        }
+
+        if self.watch_hits.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(RunState::Watchpoint(mem::take(&mut self.watch_hits))))
+        }
+    }
+
+    // Executes the instruction at the instruction pointer. Returns the state the run has to stop in, if any.
+    fn execute_instruction<T: IntcodeIo<W> + ?Sized>(&mut self, io: &mut T, stop_on_output: bool) -> Result<Option<RunState<W>>, IntcodeError> {
+        if self.tracer.is_some() {
+            self.begin_trace_record();
+        }
+        if let Some(history) = &mut self.history {
+            history.pending = Some(UndoRecord::new(self.instruction_pointer, self.relative_base));
+        }
+        // Taken before running since the instruction may jump or overwrite itself
+        let counted = if self.profile.is_some() || self.coverage.is_some() {
+            self.current_op_code().map(|op_code| (self.instruction_pointer, op_code))
+        } else {
+            None
+        };
+
+        let state = self.execute_op(io, stop_on_output)?;
+
+        // Waiting for input means the read has not happened yet, it runs again once resumed
+        if state != Some(RunState::NeedsInput) {
+            if let Some(tracer) = &mut self.tracer {
+                if let Some(record) = tracer.pending.take() {
+                    tracer.record(&record);
+                }
+            }
+            if let Some(history) = &mut self.history {
+                if let Some(record) = history.pending.take() {
+                    history.push(record);
+                }
+            }
+            if let Some((address, op_code)) = counted {
+                if let Some(profile) = &mut self.profile {
+                    profile.record(address, op_code, self.relative_base);
+                }
+                if let Some(coverage) = &mut self.coverage {
+                    coverage.record_instruction(address, op_code);
+                }
+            }
+            self.steps += 1;
+        }
+
+        Ok(state)
+    }
+
+    fn execute_op<T: IntcodeIo<W> + ?Sized>(&mut self, io: &mut T, stop_on_output: bool) -> Result<Option<RunState<W>>, IntcodeError> {
+        let instruction = match self.decode_cache.get(self.instruction_pointer) {
+            Some(decoded) => decoded,
+            None => {
+                let next_code = self.read_from_pos(self.instruction_pointer)?;
+                let decoded = Computer::decode(self.instruction_pointer, self.narrow(&next_code)?)?;
+                self.decode_cache.insert(self.instruction_pointer, decoded);
+                decoded
+            }
+        };
+
+        match instruction.op_code {
+            OpCode::Add | OpCode::Multiply => {
+                let op0 = self.read_mem(self.instruction_pointer + 1, &instruction.modes[0])?;
+                let op1 = self.read_mem(self.instruction_pointer + 2, &instruction.modes[1])?;
+
+                let result = match instruction.op_code {
+                    OpCode::Add => self.arithmetic.add(&op0, &op1),
+                    OpCode::Multiply => self.arithmetic.mul(&op0, &op1),
+                    _ => unreachable!(),
+                };
+
+                // Overflow only happens with fixed width words, which always fit an i128
+                let result = result.ok_or_else(|| IntcodeError::Overflow {
+                    address: self.instruction_pointer,
+                    op_code: instruction.op_code,
+                    left: op0.to_i128().unwrap_or_default(),
+                    right: op1.to_i128().unwrap_or_default()
+                })?;
+
+                self.store_mem(self.instruction_pointer + 3, result, &instruction.modes[2])?;
+                self.instruction_pointer += 4;
+            },
+            OpCode::ReadInput => {
+                // will read single input. If there is none we pause here so it can be pushed later
+                let input = match io.read_input() {
+                    Some(input) => input,
+                    None => return Ok(Some(RunState::NeedsInput))
+                };
+                // Input from a device cannot be given back, only the computer's own queue gets it again
+                if self.on_own_queues {
+                    if let Some(record) = self.pending_undo() {
+                        record.input = Some(input.clone());
+                    }
+                }
+                self.store_mem(self.instruction_pointer + 1, input, &instruction.modes[0])?;
+                self.instruction_pointer += 2;
+            },
+            OpCode::PrintAddress => {
+                let val = self.read_mem(self.instruction_pointer + 1, &instruction.modes[0])?;
+                self.instruction_pointer += 2;
+
+                if stop_on_output {
+                    return Ok(Some(RunState::Output(val)));
+                }
+                if self.on_own_queues {
+                    if let Some(record) = self.pending_undo() {
+                        record.queued_output = Some(val.clone());
+                    }
+                }
+                io.write_output(val);
+            },
+            OpCode::JIfTrue => {
+                if !self.read_mem(self.instruction_pointer + 1, &instruction.modes[0])?.is_zero() {
+                    let target = self.read_mem(self.instruction_pointer + 2, &instruction.modes[1])?;
+                    self.instruction_pointer = self.narrow(&target)?;
+                } else {
+                    self.instruction_pointer += 3;
+                }
+            },
+            OpCode::JIfFalse => {
+                if self.read_mem(self.instruction_pointer + 1, &instruction.modes[0])?.is_zero() {
+                    let target = self.read_mem(self.instruction_pointer + 2, &instruction.modes[1])?;
+                    self.instruction_pointer = self.narrow(&target)?;
+                } else {
+                    self.instruction_pointer += 3;
+                }
+            },
+            OpCode::Lt => {
+                let op0 = self.read_mem(self.instruction_pointer + 1, &instruction.modes[0])?;
+                let op1 = self.read_mem(self.instruction_pointer + 2, &instruction.modes[1])?;
+
+                if op0 < op1 {
+                    self.store_mem(self.instruction_pointer + 3, W::from_i32(1), &instruction.modes[2])?;
+                } else {
+                    self.store_mem(self.instruction_pointer + 3, W::zero(), &instruction.modes[2])?;
+                }
+
+                self.instruction_pointer += 4
+            }
+            OpCode::Eq => {
+                let op0 = self.read_mem(self.instruction_pointer + 1, &instruction.modes[0])?;
+                let op1 = self.read_mem(self.instruction_pointer + 2, &instruction.modes[1])?;
+
+                if op0 == op1 {
+                    self.store_mem(self.instruction_pointer + 3, W::from_i32(1), &instruction.modes[2])?;
+                } else {
+                    self.store_mem(self.instruction_pointer + 3, W::zero(), &instruction.modes[2])?;
+                }
+
+                self.instruction_pointer += 4
+            },
+            OpCode::SetRelOffset => {
+                let val = self.read_mem(self.instruction_pointer + 1, &instruction.modes[0])?;
+                self.relative_base += self.narrow(&val)?;
+
+                self.instruction_pointer += 2;
+            },
+            OpCode::Halt => {
+                return Ok(Some(RunState::Halted));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn read_mem(&mut self, pos: i128, param_mode: &ParamModes) -> Result<W, IntcodeError> {
+        match *param_mode {
+            ParamModes::ImmediateMode => self.read_from_pos(pos),
+            ParamModes::PositionMode => {
+                let idx_value = self.read_from_pos(pos)?;
+                self.read_data(self.narrow(&idx_value)?)
+            },
+            ParamModes::RelativeMode => {
+                let idx_value = self.read_from_pos(pos)?;
+                self.read_data(self.narrow(&idx_value)? + self.relative_base)
+            }
+        }
+    }
+
+    pub fn store_mem(&mut self, pos: i128, value: W, param_mode: &ParamModes) -> Result<(), IntcodeError> {
+        match *param_mode {
+            ParamModes::ImmediateMode => Err(IntcodeError::WriteInImmediateMode { address: self.instruction_pointer }),
+            ParamModes::PositionMode => {
+                let idx_value = self.read_from_pos(pos)?;
+                self.store_data(self.narrow(&idx_value)?, value)
+            },
+            ParamModes::RelativeMode => {
+                let idx_value = self.read_from_pos(pos)?;
+                self.store_data(self.narrow(&idx_value)? + self.relative_base, value)
+            }
+        }
+    }
+
+    // Words used as opcodes, addresses or offsets have to fit in the i128 the machine counts in
+    fn narrow(&self, word: &W) -> Result<i128, IntcodeError> {
+        word.to_i128().ok_or(IntcodeError::WordOutOfRange { address: self.instruction_pointer })
+    }
+
+    // Watches the data reads and/or writes the program does on positions. Instruction fetches and
+    // immediate operands are not data accesses, and neither are peek and poke.
+    pub fn watch(&mut self, positions: RangeInclusive<i128>, access: Access) -> usize {
+        self.add_watchpoint(positions, access, WatchAction::Pause)
+    }
+
+    pub fn watch_with<F: FnMut(&WatchEvent<W>) + Send + 'static>(&mut self, positions: RangeInclusive<i128>, access: Access, callback: F) -> usize {
+        self.add_watchpoint(positions, access, WatchAction::Callback(Box::new(callback)))
+    }
+
+    pub fn unwatch(&mut self, id: usize) -> bool {
+        let before = self.watchpoints.len();
+        self.watchpoints.retain(|watchpoint| watchpoint.id != id);
+        self.watchpoints.len() != before
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint<W>] {
+        &self.watchpoints
+    }
+
+    fn add_watchpoint(&mut self, positions: RangeInclusive<i128>, access: Access, action: WatchAction<W>) -> usize {
+        let id = self.next_watch_id;
+        self.next_watch_id += 1;
+        self.watchpoints.push(Watchpoint { id, positions, access, action });
+
+        id
+    }
+
+    fn read_data(&mut self, pos: i128) -> Result<W, IntcodeError> {
+        let value = self.read_from_pos(pos)?;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.read.insert(pos);
+        }
+        if !self.watchpoints.is_empty() {
+            self.notify_watchpoints(pos, Access::Read, &value, &value);
+        }
+
+        Ok(value)
+    }
+
+    fn store_data(&mut self, pos: i128, value: W) -> Result<(), IntcodeError> {
+        // Only worth copying the old value out if someone is going to look at it
+        let old = if self.history.is_some() || !self.watchpoints.is_empty() { Some(self.peek(pos)) } else { None };
+        if let (Some(old), Some(record)) = (&old, self.pending_undo()) {
+            record.writes.push((pos, old.clone()));
+        }
+        self.store_in_pos(pos, value.clone())?;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.written.insert(pos);
+        }
+        if let Some(record) = self.tracer.as_mut().and_then(|tracer| tracer.pending.as_mut()) {
+            record.writes.push((pos, value.clone()));
+        }
+        if let (Some(old), false) = (&old, self.watchpoints.is_empty()) {
+            self.notify_watchpoints(pos, Access::Write, old, &value);
+        }
+
+        Ok(())
+    }
+
+    // Every instruction executed from now on is written to out. Replaces any trace already running
+    // without finishing it, so call finish_trace first to see its errors.
+    pub fn trace_to<O: Write + Send + 'static>(&mut self, out: O, format: TraceFormat) {
+        self.tracer = Some(Tracer::new(Box::new(out), format));
+    }
+
+    // Stops tracing, flushing the output and reporting the first write error, if any
+    pub fn finish_trace(&mut self) -> std::io::Result<()> {
+        match self.tracer.take() {
+            Some(tracer) => tracer.finish(),
+            None => Ok(())
+        }
+    }
+
+    // Checked unless set otherwise
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    // Limits apply from now on, replacing any earlier budget
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = Some(BudgetTracker::new(budget, self.steps));
+    }
+
+    pub fn clear_budget(&mut self) {
+        self.budget = None;
+    }
+
+    pub fn budget(&self) -> Option<Budget> {
+        self.budget.as_ref().map(|tracker| tracker.budget)
+    }
+
+    // Starts counting from scratch
+    pub fn enable_profile(&mut self) {
+        self.profile = Some(Profile::new(self.relative_base));
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    // Stops profiling and hands back what was collected
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    fn current_op_code(&self) -> Option<OpCode> {
+        if let Some(decoded) = self.decode_cache.get(self.instruction_pointer) {
+            return Some(decoded.op_code);
+        }
+
+        let code = self.peek(self.instruction_pointer).to_i128()?;
+        Computer::decode(self.instruction_pointer, code).ok().map(|decoded| decoded.op_code)
+    }
+
+    // Number of instructions executed so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    // Keeps what is needed to undo the last limit instructions. Turning it on again drops the old records.
+    pub fn enable_history(&mut self, limit: usize) {
+        self.history = Some(History::new(limit));
+    }
+
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+
+    // How many instructions can be rewound
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, |history| history.records.len())
+    }
+
+    // Undoes up to steps instructions, returning how many were undone. Consumed input goes back to the
+    // front of the input queue and outputs still sitting in the output queue are taken back. Outputs
+    // already read, or handed to a device, cannot be.
+    pub fn rewind(&mut self, steps: usize) -> usize {
+        let mut rewound = 0;
+        while rewound < steps {
+            match self.history.as_mut().and_then(|history| history.records.pop_back()) {
+                Some(record) => self.undo(record),
+                None => break
+            }
+            rewound += 1;
+        }
+
+        rewound
+    }
+
+    // Rewinds to just before the most recent instruction that wrote pos and returns its step number.
+    // Nothing is undone if no recorded instruction wrote there.
+    pub fn rewind_to_last_write(&mut self, pos: i128) -> Option<u64> {
+        let records = &self.history.as_ref()?.records;
+        let idx = records.iter().rposition(|record| record.writes.iter().any(|(written, _)| *written == pos))?;
+
+        self.rewind(records.len() - idx);
+        Some(self.steps)
+    }
+
+    fn undo(&mut self, record: UndoRecord<W>) {
+        for (pos, previous) in record.writes.into_iter().rev() {
+            self.memory.set(pos, previous);
+            self.decode_cache.invalidate(pos);
+        }
+
+        if let Some(input) = record.input {
+            self.queues.input.push_front(input);
+        }
+        if let Some(output) = record.queued_output {
+            if self.queues.output.back() == Some(&output) {
+                self.queues.output.pop_back();
+            }
+        }
+
+        self.instruction_pointer = record.instruction_pointer;
+        self.relative_base = record.relative_base;
+        self.steps -= 1;
+        self.watch_hits.clear();
+    }
+
+    fn pending_undo(&mut self) -> Option<&mut UndoRecord<W>> {
+        self.history.as_mut().and_then(|history| history.pending.as_mut())
+    }
+
+    // Resolves the operands without touching memory so tracing cannot change what the program sees.
+    // Undecodable instructions are left alone, executing them reports the error.
+    fn begin_trace_record(&mut self) {
+        let ip = self.instruction_pointer;
+        let instruction = match self.peek(ip).to_i128().map(|code| Computer::get_instruction(ip, code)) {
+            Some(Ok(instruction)) => instruction,
+            _ => return
+        };
+
+        // A parameter that does not fit an address is left as the raw word
+        let write_parameter = instruction.op_code.write_parameter();
+        let operands = instruction.param_modes.iter().enumerate()
+            .map(|(idx, mode)| {
+                let word = self.peek(ip + 1 + idx as i128);
+                let position = match (mode, word.to_i128()) {
+                    (ParamModes::ImmediateMode, _) | (_, None) => return word,
+                    (ParamModes::PositionMode, Some(position)) => position,
+                    (ParamModes::RelativeMode, Some(offset)) => offset + self.relative_base
+                };
+
+                if write_parameter == Some(idx) {
+                    W::from_i128(position).unwrap_or(word)
+                } else {
+                    self.peek(position)
+                }
+            })
+            .collect();
+
+        let record = TraceRecord {
+            step: self.steps,
+            ip,
+            op_code: instruction.op_code,
+            modes: instruction.param_modes,
+            operands,
+            writes: Vec::new(),
+            rb: self.relative_base
+        };
+
+        if let Some(tracer) = &mut self.tracer {
+            tracer.pending = Some(record);
+        }
+    }
+
+    fn notify_watchpoints(&mut self, position: i128, access: Access, old: &W, new: &W) {
+        for watchpoint in self.watchpoints.iter_mut().filter(|w| w.matches(position, access)) {
+            let event = WatchEvent {
+                id: watchpoint.id,
+                address: self.instruction_pointer,
+                position,
+                access,
+                old: old.clone(),
+                new: new.clone()
+            };
+
+            match &mut watchpoint.action {
+                WatchAction::Pause => self.watch_hits.push(event),
+                WatchAction::Callback(callback) => callback(&event)
+            }
+        }
+    }
+
+    pub fn push_input(&mut self, input: W) {
+        self.queues.input.push_back(input);
+    }
+
+    pub fn read_output(&mut self) -> Option<W> {
+        self.queues.output.pop_front()
+    }
+
+    pub fn instruction_pointer(&self) -> i128 {
+        self.instruction_pointer
+    }
+
+    pub fn relative_base(&self) -> i128 {
+        self.relative_base
+    }
+
+    pub fn pending_input(&self) -> &VecDeque<W> {
+        &self.queues.input
+    }
+
+    pub fn pending_output(&self) -> &VecDeque<W> {
+        &self.queues.output
+    }
+
+    // Looks at memory from outside the program. Cells never touched read as zero.
+    pub fn peek(&self, pos: i128) -> W {
+        self.memory.get(pos)
+    }
+
+    pub fn poke(&mut self, pos: i128, value: W) -> Result<(), IntcodeError> {
+        self.store_in_pos(pos, value)
+    }
+
+    // Dense copy of memory from address 0 up to the highest address in use
+    pub fn dump_memory(&self) -> Vec<W> {
+        let len = self.memory.len();
+        (0..len).map(|pos| self.peek(pos)).collect()
+    }
+
+    fn read_from_pos(&self, pos: i128) -> Result<W, IntcodeError> {
+        if pos < 0 {
+            return Err(IntcodeError::NegativeAddress { address: self.instruction_pointer, position: pos });
+        }
+
+        Ok(self.memory.get(pos))
+    }
+
+    fn store_in_pos(&mut self, pos: i128, value: W) -> Result<(), IntcodeError> {
+        if pos < 0 {
+            return Err(IntcodeError::NegativeAddress { address: self.instruction_pointer, position: pos });
+        }
+
+        self.memory.set(pos, value);
+        self.decode_cache.invalidate(pos);
+        Ok(())
+    }
+}
+
+// Decoding only looks at the opcode word, which always fits an i128 by the time it gets here
+impl Computer {
+
+    // Decodes the word found at address. The address is only used to report errors.
+    pub fn get_instruction(address: i128, code: i128) -> Result<Instruction, IntcodeError> {
+        let decoded = Computer::decode(address, code)?;
+        let param_count = Computer::get_number_parameters(&decoded.op_code) as usize;
+
+        Ok(Instruction {
+            op_code: decoded.op_code,
+            param_modes: decoded.modes[..param_count].to_vec()
+        })
+    }
+
+    fn decode(address: i128, code: i128) -> Result<Decoded, IntcodeError> {
+        let op_code = Computer::get_op_code(address, code)?;
+        let mut mode_codes = code / 100;
+
+        let param_count = Computer::get_number_parameters(&op_code) as usize;
+
+        // Read the parameters modes
+        let mut modes = [ParamModes::PositionMode; 3];
+        for mode in modes.iter_mut().take(param_count) {
+            if mode_codes == 0 {
+                break;
+            }
+
+            *mode = Computer::get_param_mode(address, mode_codes % 10)?;
+            mode_codes /= 10; // read the next parameter mode
+        }
+
+        Ok(Decoded {
+            op_code,
+            modes
+        })
+    }
+
+    fn get_op_code(address: i128, code: i128) -> Result<OpCode, IntcodeError> {
+        match code % 100 {
+            1 => Ok(OpCode::Add),
+            2 => Ok(OpCode::Multiply),
+            3 => Ok(OpCode::ReadInput),
+            4 => Ok(OpCode::PrintAddress),
+            5 => Ok(OpCode::JIfTrue),
+            6 => Ok(OpCode::JIfFalse),
+            7 => Ok(OpCode::Lt),
+            8 => Ok(OpCode::Eq),
+            9 => Ok(OpCode::SetRelOffset),
+            99 => Ok(OpCode::Halt),
+            _ => Err(IntcodeError::InvalidOpCode { address, code })
+        }
+    }
+
+    fn get_param_mode(address: i128, value: i128) -> Result<ParamModes, IntcodeError> {
+        match value {
+            0 => Ok(ParamModes::PositionMode),
+            1 => Ok(ParamModes::ImmediateMode),
+            2 => Ok(ParamModes::RelativeMode),
+            _ => Err(IntcodeError::InvalidParamMode { address, mode: value })
+        }
+    }
+
+    pub fn get_number_parameters(opcode: &OpCode) -> i32 {
+        match opcode {
+            OpCode::Add => 3,
+            OpCode::Multiply => 3,
+            OpCode::ReadInput => 1,
+            OpCode::PrintAddress => 1,
+            OpCode::JIfTrue => 2,
+            OpCode::JIfFalse => 2,
+            OpCode::Lt => 3,
+            OpCode::Eq => 3,
+            OpCode::SetRelOffset => 1,
+            OpCode::Halt => 0
+        }
+    }
+}
+
+// Forks the machine state and its arithmetic policy: the clone starts without watchpoints or a trace
+impl<W: Word> Clone for Computer<W> {
+    fn clone(&self) -> Computer<W> {
+        let mut computer = Computer::from_snapshot(self.snapshot());
+        computer.arithmetic = self.arithmetic;
+        computer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+
+    fn write_event(id: usize, address: i128, position: i128, old: i128, new: i128) -> WatchEvent {
+        WatchEvent { id, address, position, access: Access::Write, old, new }
+    }
+
+    #[test]
+    fn run_to_halt_reports_the_step_budget() {
+        let mut computer = Computer::new(vec![1105, 1, 0]);
+        computer.set_budget(Budget { max_steps: Some(10), ..Budget::default() });
+
+        assert_eq!(computer.run_to_halt(), Err(IntcodeError::BudgetExceeded { address: 0, limit: Limit::Steps }));
+        assert_eq!(computer.steps(), 10);
+    }
+
+    #[test]
+    fn run_to_halt_reports_missing_input() {
+        let mut computer = Computer::new(vec![3, 0, 99]);
+        assert_eq!(computer.run_to_halt(), Err(IntcodeError::InputExhausted { address: 0 }));
+    }
+
+    #[test]
+    fn time_limit_is_checked_between_batches_of_steps() {
+        let mut computer = Computer::new(vec![1105, 1, 0]);
+        computer.set_budget(Budget { timeout: Some(Duration::from_millis(20)), ..Budget::default() });
+
+        assert_eq!(computer.run(), Ok(RunState::BudgetExceeded(Limit::Time)));
+        assert!(computer.steps() > 0);
+        assert!(computer.steps().is_multiple_of(budget::CLOCK_CHECK_INTERVAL));
+
+        // Stays stopped until the budget changes
+        assert_eq!(computer.run(), Ok(RunState::BudgetExceeded(Limit::Time)));
+        computer.set_budget(Budget { max_steps: Some(3), ..Budget::default() });
+        let steps = computer.steps();
+        assert_eq!(computer.run(), Ok(RunState::BudgetExceeded(Limit::Steps)));
+        assert_eq!(computer.steps(), steps + 3);
+    }
+
+    #[test]
+    fn pause_watchpoint_stops_after_the_instruction() {
+        let mut computer = Computer::new(vec![1, 0, 0, 0, 99]);
+        let id = computer.watch(0..=0, Access::Write);
+
+        assert_eq!(computer.run(), Ok(RunState::Watchpoint(vec![write_event(id, 0, 0, 1, 2)])));
+        assert_eq!(computer.instruction_pointer(), 4);
+        assert_eq!(computer.run(), Ok(RunState::Halted));
+    }
+
+    #[test]
+    fn run_to_halt_runs_past_pause_watchpoints() {
+        let mut computer = Computer::new(vec![1, 0, 0, 0, 99]);
+        computer.watch(0..=0, Access::Write);
+
+        assert_eq!(computer.run_to_halt(), Ok(()));
+        assert_eq!(computer.peek(0), 2);
+    }
+
+    #[test]
+    fn callback_watchpoint_does_not_stop() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut computer = Computer::new(vec![1, 0, 0, 0, 1, 0, 0, 0, 99]);
+        let log = Arc::clone(&seen);
+        let id = computer.watch_with(0..=0, Access::Write, move |event| log.lock().unwrap().push(event.clone()));
+
+        assert_eq!(computer.run(), Ok(RunState::Halted));
+        assert_eq!(*seen.lock().unwrap(), vec![write_event(id, 0, 0, 1, 2), write_event(id, 4, 0, 2, 4)]);
+    }
+
+    #[test]
+    fn watchpoint_hit_by_an_output_is_reported_on_the_next_call() {
+        let mut computer = Computer::new(vec![4, 0, 99]);
+        let id = computer.watch(0..=0, Access::Read);
+        let read = WatchEvent { id, address: 0, position: 0, access: Access::Read, old: 4, new: 4 };
+
+        assert_eq!(computer.run_until_output(), Ok(RunState::Output(4)));
+        assert_eq!(computer.run_until_output(), Ok(RunState::Watchpoint(vec![read])));
+        assert_eq!(computer.run_until_output(), Ok(RunState::Halted));
+    }
+
+    // Runs the add at 0, rewrites it into a mul and jumps back to run it again, then halts.
+    // [20] ends up 5 + 6 after the first pass and 5 * 6 after the second.
+    fn rewrites_its_first_instruction() -> Vec<i128> {
+        vec![1101, 5, 6, 20, 1005, 21, 18, 1101, 1, 0, 21, 1101, 1102, 0, 0, 1105, 1, 0, 99, 0, 0, 0]
+    }
+
+    #[test]
+    fn rewritten_instruction_is_decoded_again() {
+        let mut computer = Computer::new(rewrites_its_first_instruction());
+        assert_eq!(computer.run_to_halt(), Ok(()));
+        assert_eq!(computer.peek(20), 30);
+    }
+
+    #[test]
+    fn poke_drops_the_decoded_instruction() {
+        // add [0] into [10], wait for input, jump back
+        let mut computer = Computer::new(vec![1101, 2, 3, 10, 3, 11, 1105, 1, 0, 99, 0, 0]);
+        assert_eq!(computer.run(), Ok(RunState::NeedsInput));
+        assert_eq!(computer.peek(10), 5);
+
+        computer.poke(0, 1102).unwrap();
+        computer.push_input(0);
+        assert_eq!(computer.run(), Ok(RunState::NeedsInput));
+        assert_eq!(computer.peek(10), 6);
+    }
+
+    #[test]
+    fn rewind_drops_the_decoded_instruction() {
+        let mut computer = Computer::new(rewrites_its_first_instruction());
+        computer.enable_history(100);
+        computer.run_to_halt().unwrap();
+
+        // Address 0 was last decoded as the mul, rewinding puts the add back
+        computer.rewind(100);
+        assert_eq!((computer.instruction_pointer(), computer.peek(0), computer.peek(20)), (0, 1101, 0));
+        computer.step().unwrap();
+        assert_eq!(computer.peek(20), 11);
+    }
+
+    #[test]
+    fn restore_drops_the_decoded_instructions() {
+        let mut computer = Computer::new(rewrites_its_first_instruction());
+        let start = computer.snapshot();
+        computer.run_to_halt().unwrap();
+
+        computer.restore(&start);
+        computer.step().unwrap();
+        assert_eq!(computer.peek(20), 11);
+    }
+
+    #[test]
+    fn rewind_puts_input_back_in_order() {
+        let mut computer = Computer::new(vec![3, 10, 3, 11, 1, 10, 11, 12, 99]);
+        computer.enable_history(10);
+        computer.push_input(4);
+        computer.push_input(5);
+        computer.run_to_halt().unwrap();
+        assert_eq!((computer.steps(), computer.peek(12)), (4, 9));
+
+        // Back to before the second read
+        assert_eq!(computer.rewind(3), 3);
+        assert_eq!((computer.steps(), computer.instruction_pointer()), (1, 2));
+        assert_eq!(computer.pending_input(), &VecDeque::from(vec![5]));
+        assert_eq!((computer.peek(10), computer.peek(11), computer.peek(12)), (4, 0, 0));
+
+        assert_eq!(computer.rewind(1), 1);
+        assert_eq!(computer.pending_input(), &VecDeque::from(vec![4, 5]));
+
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.peek(12), 9);
+    }
+
+    #[test]
+    fn rewind_after_run_with_leaves_the_queues_alone() {
+        let mut computer = Computer::new(vec![3, 20, 3, 21, 1, 20, 21, 22, 4, 22, 99]);
+        computer.enable_history(10);
+        let mut io = IterIo::new(vec![4, 5]);
+        assert_eq!(computer.run_with(&mut io), Ok(RunState::Halted));
+        assert_eq!(io.output, vec![9]);
+
+        // The device already handed its input over, so there is nothing to put back
+        assert_eq!(computer.rewind(5), 5);
+        assert_eq!(computer.steps(), 0);
+        assert!(computer.pending_input().is_empty());
+        assert!(computer.pending_output().is_empty());
+
+        computer.push_input(1);
+        computer.push_input(2);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.pending_output(), &VecDeque::from(vec![3]));
+    }
+
+    #[test]
+    fn rewind_takes_back_queued_output() {
+        let mut computer = Computer::new(vec![104, 7, 104, 8, 99]);
+        computer.enable_history(10);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.pending_output(), &VecDeque::from(vec![7, 8]));
+
+        computer.rewind(2);
+        assert_eq!(computer.pending_output(), &VecDeque::from(vec![7]));
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.pending_output(), &VecDeque::from(vec![7, 8]));
+    }
+
+    #[test]
+    fn rewind_leaves_output_already_read() {
+        let mut computer = Computer::new(vec![104, 7, 104, 8, 99]);
+        computer.enable_history(10);
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.read_output(), Some(7));
+
+        // 8 is still queued so it is taken back, 7 is gone already
+        computer.rewind(2);
+        assert!(computer.pending_output().is_empty());
+        computer.rewind(1);
+        assert!(computer.pending_output().is_empty());
+
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.pending_output(), &VecDeque::from(vec![7, 8]));
+    }
+
+    #[test]
+    fn rewind_stops_at_the_history_limit() {
+        let mut computer = Computer::new(vec![1101, 1, 1, 20, 1101, 2, 2, 21, 1101, 3, 3, 22, 99]);
+        computer.enable_history(2);
+        computer.run_to_halt().unwrap();
+        assert_eq!((computer.steps(), computer.history_len()), (4, 2));
+
+        assert_eq!(computer.rewind(10), 2);
+        assert_eq!((computer.steps(), computer.instruction_pointer(), computer.history_len()), (2, 8, 0));
+        assert_eq!(computer.peek(22), 0);
+        assert_eq!(computer.rewind(1), 0);
+    }
+
+    #[test]
+    fn rewind_to_last_write() {
+        let mut computer = Computer::new(vec![1101, 1, 1, 20, 1101, 2, 2, 21, 1101, 3, 3, 20, 99]);
+        computer.enable_history(10);
+        computer.run_to_halt().unwrap();
+
+        assert_eq!(computer.rewind_to_last_write(21), Some(1));
+        assert_eq!((computer.instruction_pointer(), computer.peek(20), computer.peek(21)), (4, 2, 0));
+
+        // Nothing recorded writes there, so nothing moves
+        assert_eq!(computer.rewind_to_last_write(22), None);
+        assert_eq!(computer.steps(), 1);
+
+        assert_eq!(computer.rewind_to_last_write(20), Some(0));
+        assert_eq!(computer.peek(20), 0);
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use crate::Word;
+
+const PAGE_BITS: u32 = 10;
+const PAGE_SIZE: usize = 1 << PAGE_BITS;
+
+// The program image lives in a plain vector. Anything past it goes to pages that are only allocated
+// when first written, so far addresses (relative base tricks, day 9's 1000+) stay cheap. Reading a
+// cell nobody wrote is zero and allocates nothing.
+//
+// Two memories are equal when every address reads the same, so where the image ends does not matter.
+#[derive(Debug, Clone)]
+pub struct Memory<W = i128> {
+    image: Vec<W>,
+    pages: HashMap<i128, Box<[W]>>,
+    // One past the highest cell ever given something other than zero, at least the image
+    len: i128
+}
+
+impl<W: Word> Memory<W> {
+    pub fn new(image: Vec<W>) -> Memory<W> {
+        Memory {
+            len: image.len() as i128,
+            image,
+            pages: HashMap::new()
+        }
+    }
+
+    // Builds memory from (position, value) pairs. The run starting at 0 becomes the image.
+    pub fn from_cells<I: IntoIterator<Item = (i128, W)>>(cells: I) -> Memory<W> {
+        let mut cells: Vec<(i128, W)> = cells.into_iter().collect();
+        cells.sort_by_key(|(pos, _)| *pos);
+
+        let image_len = cells.iter().enumerate()
+            .take_while(|(idx, (pos, _))| *pos == *idx as i128)
+            .count();
+
+        let mut memory = Memory::new(cells[..image_len].iter().map(|(_, value)| value.clone()).collect());
+        for (pos, value) in cells[image_len..].iter() {
+            memory.set(*pos, value.clone());
+        }
+
+        memory
+    }
+
+    pub fn get(&self, pos: i128) -> W {
+        if pos >= 0 && pos < self.image.len() as i128 {
+            return self.image[pos as usize].clone();
+        }
+
+        match self.pages.get(&(pos >> PAGE_BITS)) {
+            Some(page) => page[(pos & (PAGE_SIZE as i128 - 1)) as usize].clone(),
+            None => W::zero()
+        }
+    }
+
+    pub fn set(&mut self, pos: i128, value: W) {
+        if pos >= 0 && pos < self.image.len() as i128 {
+            self.image[pos as usize] = value;
+            return;
+        }
+
+        // Writing a zero to a page that does not exist yet changes nothing
+        if value.is_zero() && !self.pages.contains_key(&(pos >> PAGE_BITS)) {
+            return;
+        }
+
+        if !value.is_zero() && pos >= self.len {
+            self.len = pos + 1;
+        }
+        let page = self.pages.entry(pos >> PAGE_BITS).or_insert_with(|| vec![W::zero(); PAGE_SIZE].into_boxed_slice());
+        page[(pos & (PAGE_SIZE as i128 - 1)) as usize] = value;
+    }
+
+    pub(crate) fn image_len(&self) -> usize {
+        self.image.len()
+    }
+
+    // Cells memory is taking up, whether they hold anything or not
+    pub fn allocated_cells(&self) -> usize {
+        self.image.len() + self.pages.len() * PAGE_SIZE
+    }
+
+    // Every image cell plus the non zero cells of the pages, in address order
+    pub fn cells(&self) -> Vec<(i128, W)> {
+        let mut cells: Vec<(i128, W)> = self.image.iter().enumerate()
+            .map(|(pos, value)| (pos as i128, value.clone()))
+            .collect();
+        cells.extend(self.page_cells().map(|(pos, value)| (pos, value.clone())));
+
+        cells
+    }
+
+    // The non zero page cells, in address order. Pages can overlap the end of the image, those
+    // cells are never used.
+    fn page_cells(&self) -> impl Iterator<Item = (i128, &W)> {
+        let mut page_numbers: Vec<i128> = self.pages.keys().copied().collect();
+        page_numbers.sort_unstable();
+
+        let image_len = self.image.len() as i128;
+        page_numbers.into_iter().flat_map(move |page_number| {
+            let base = page_number << PAGE_BITS;
+            self.pages[&page_number].iter().enumerate()
+                .map(move |(offset, value)| (base + offset as i128, value))
+                .filter(move |(pos, value)| !value.is_zero() && *pos >= image_len)
+        })
+    }
+
+    fn non_zero_cells(&self) -> impl Iterator<Item = (i128, &W)> {
+        self.image.iter().enumerate()
+            .map(|(pos, value)| (pos as i128, value))
+            .filter(|(_, value)| !value.is_zero())
+            .chain(self.page_cells())
+    }
+
+    // One past the last cell that is part of the image or was given something. A cell that was
+    // set back to zero still counts.
+    pub fn len(&self) -> i128 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<W: Word> Default for Memory<W> {
+    fn default() -> Memory<W> {
+        Memory::new(Vec::new())
+    }
+}
+
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Memory<W>) -> bool {
+        self.non_zero_cells().eq(other.non_zero_cells())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_past_the_image_are_zero() {
+        let memory = Memory::new(vec![1, 2, 3]);
+        assert_eq!((memory.get(2), memory.get(3), memory.get(1 << 40)), (3, 0, 0));
+        assert_eq!(memory.allocated_cells(), 3);
+        assert_eq!(memory.len(), 3);
+    }
+
+    #[test]
+    fn pages_are_allocated_on_first_write() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        memory.set(5000, 0);
+        assert_eq!(memory.allocated_cells(), 3);
+
+        memory.set(5000, 7);
+        memory.set(5001, 8);
+        assert_eq!(memory.allocated_cells(), 3 + PAGE_SIZE);
+        assert_eq!((memory.get(5000), memory.get(5001), memory.get(4999)), (7, 8, 0));
+
+        memory.set(5000 + PAGE_SIZE as i128, 9);
+        assert_eq!(memory.allocated_cells(), 3 + 2 * PAGE_SIZE);
+        assert_eq!(memory.cells(), vec![(0, 1), (1, 2), (2, 3), (5000, 7), (5001, 8), (5000 + PAGE_SIZE as i128, 9)]);
+    }
+
+    #[test]
+    fn page_overlapping_the_image() {
+        let mut memory = Memory::new(vec![5; 10]);
+        memory.set(10, 1);
+        memory.set(3, 4);
+        assert_eq!((memory.get(3), memory.get(10)), (4, 1));
+        assert_eq!(memory.cells().len(), 11);
+    }
+
+    #[test]
+    fn len_follows_the_highest_write() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        memory.set(100, 0);
+        assert_eq!(memory.len(), 3);
+        memory.set(100, 1);
+        assert_eq!(memory.len(), 101);
+        memory.set(50, 1);
+        assert_eq!(memory.len(), 101);
+        assert!(!memory.is_empty());
+        assert!(Memory::<i128>::default().is_empty());
+    }
+
+    #[test]
+    fn equal_when_every_address_reads_the_same() {
+        let mut paged = Memory::new(vec![1, 2]);
+        paged.set(3, 4);
+        let mut longer = Memory::new(vec![1, 2, 0, 4, 0, 0]);
+        assert_eq!(paged, longer);
+
+        // A zero left in a page counts the same as one in the image
+        paged.set(3, 0);
+        longer.set(3, 0);
+        assert_eq!(paged, longer);
+        assert_eq!(paged, Memory::new(vec![1, 2]));
+
+        longer.set(5, 6);
+        assert_ne!(paged, longer);
+        paged.set(5, 6);
+        assert_eq!(paged, longer);
+        assert_ne!(Memory::new(vec![1, 2]), Memory::new(vec![2, 1]));
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::VecDeque;
+
+use crate::budget::BudgetTracker;
+use crate::{Budget, Computer, IntcodeError, IntcodeIo, RunState, Word};
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Packet<W = i128> {
+    pub from: i128,
+    pub dest: i128,
+    pub x: W,
+    pub y: W
+}
+
+pub enum IdleAction<W = i128> {
+    // Delivered before the next round. Injecting nothing leaves the network idle, which ends the run.
+    Inject(Vec<Packet<W>>),
+    Stop
+}
+
+// Sits at an address of its own, gets every packet sent there and decides what happens when the
+// network goes idle, like the NAT in day 23
+pub trait NetworkMonitor<W = i128> {
+    fn receive(&mut self, packet: Packet<W>);
+    fn idle(&mut self) -> IdleAction<W>;
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum NetworkStop {
+    // Went idle with no monitor to wake it up, or one that injected nothing
+    Idle,
+    MonitorStopped,
+    AllHalted,
+    RoundLimit
+}
+
+struct Node<W: Word> {
+    computer: Computer<W>,
+    inbox: VecDeque<W>,
+    // Words of a packet that is only partly written
+    outbox: Vec<W>,
+    halted: bool
+}
+
+// What a machine sees during its turn. An empty queue reads as -1 once, asking again ends the turn.
+struct TurnIo<'a, W> {
+    address: i128,
+    inbox: &'a mut VecDeque<W>,
+    outbox: &'a mut Vec<W>,
+    sent: Vec<Packet<W>>,
+    received: bool,
+    polled: bool
+}
+
+impl<W: Word> IntcodeIo<W> for TurnIo<'_, W> {
+    fn read_input(&mut self) -> Option<W> {
+        if let Some(value) = self.inbox.pop_front() {
+            self.received = true;
+            return Some(value);
+        }
+        if self.polled {
+            return None;
+        }
+
+        self.polled = true;
+        Some(W::from_i32(-1))
+    }
+
+    fn write_output(&mut self, value: W) {
+        self.outbox.push(value);
+        if self.outbox.len() == 3 {
+            let mut words = self.outbox.drain(..);
+            let dest = words.next().unwrap();
+            let (x, y) = (words.next().unwrap(), words.next().unwrap());
+
+            // A destination too big for any address cannot reach anyone
+            self.sent.push(Packet { from: self.address, dest: dest.to_i128().unwrap_or(i128::MIN), x, y });
+        }
+    }
+}
+
+// Machines are given the addresses 0, 1, ... in order and read their own address as their first
+// input. Each round every machine that has not halted gets a turn, in address order, which lasts
+// until it asks for input with nothing queued a second time. Packets are delivered as soon as
+// the turn that sent them ends, so runs are the same every time. A machine that never asks for
+// input would keep its turn forever, set_turn_limit cuts such turns short.
+pub struct PacketNetwork<W: Word = i128> {
+    nodes: Vec<Node<W>>,
+    monitor: Option<(i128, Box<dyn NetworkMonitor<W> + Send>)>,
+    undeliverable: Vec<Packet<W>>,
+    rounds: u64,
+    turn_limit: Option<u64>
+}
+
+impl<W: Word> PacketNetwork<W> {
+    pub fn new(machines: Vec<Computer<W>>) -> PacketNetwork<W> {
+        let nodes = machines.into_iter()
+            .enumerate()
+            .map(|(address, computer)| Node {
+                computer,
+                inbox: VecDeque::from(vec![W::from_i32(address as i32)]),
+                outbox: Vec::new(),
+                halted: false
+            })
+            .collect();
+
+        PacketNetwork {
+            nodes,
+            monitor: None,
+            undeliverable: Vec::new(),
+            rounds: 0,
+            turn_limit: None
+        }
+    }
+
+    // Ends a turn after this many instructions, and the machine carries on next round. A machine
+    // stopped this way is busy, so the network does not count as idle that round. Its own budget is
+    // put aside for the turn. Without a turn limit, a machine running out of its own budget is an
+    // error, since it would stop every turn and look idle.
+    pub fn set_turn_limit(&mut self, steps: u64) {
+        self.turn_limit = Some(steps);
+    }
+
+    pub fn set_monitor(&mut self, address: i128, monitor: Box<dyn NetworkMonitor<W> + Send>) {
+        self.monitor = Some((address, monitor));
+    }
+
+    pub fn send(&mut self, packet: Packet<W>) {
+        let dest = packet.dest;
+        if dest >= 0 && (dest as usize) < self.nodes.len() {
+            let inbox = &mut self.nodes[dest as usize].inbox;
+            inbox.push_back(packet.x);
+            inbox.push_back(packet.y);
+            return;
+        }
+
+        match &mut self.monitor {
+            Some((address, monitor)) if *address == dest => monitor.receive(packet),
+            _ => self.undeliverable.push(packet)
+        }
+    }
+
+    // Packets sent to addresses nobody has
+    pub fn undeliverable(&self) -> &[Packet<W>] {
+        &self.undeliverable
+    }
+
+    pub fn machine(&self, address: usize) -> &Computer<W> {
+        &self.nodes[address].computer
+    }
+
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    // Gives every machine one turn. Returns whether the network was idle: no packet was sent or
+    // read, and all queues are empty.
+    pub fn round(&mut self) -> Result<bool, IntcodeError> {
+        let mut idle = true;
+
+        for address in 0..self.nodes.len() {
+            let node = &mut self.nodes[address];
+            if node.halted {
+                continue;
+            }
+
+            // A limited turn runs on a budget of its own
+            let own_budget = self.turn_limit.map(|steps| {
+                let budget = Budget { max_steps: Some(steps), ..Budget::default() };
+                let turn = BudgetTracker::new(budget, node.computer.steps());
+                node.computer.budget.replace(turn)
+            });
+
+            let mut io = TurnIo {
+                address: address as i128,
+                inbox: &mut node.inbox,
+                outbox: &mut node.outbox,
+                sent: Vec::new(),
+                received: false,
+                polled: false
+            };
+            let state = node.computer.run_with(&mut io);
+            if let Some(own_budget) = own_budget {
+                node.computer.budget = own_budget;
+            }
+
+            // Watchpoints and turn limits only pause a machine, it carries on next round
+            let state = state?;
+            let address = node.computer.instruction_pointer();
+            if state == RunState::Halted {
+                node.halted = true;
+            }
+            let cut_short = self.turn_limit.is_some() && matches!(state, RunState::BudgetExceeded(_));
+
+            idle &= !io.received && io.sent.is_empty() && !cut_short;
+            for packet in io.sent {
+                self.send(packet);
+            }
+
+            if let RunState::BudgetExceeded(limit) = state {
+                if !cut_short {
+                    return Err(IntcodeError::BudgetExceeded { address, limit });
+                }
+            }
+        }
+
+        self.rounds += 1;
+        Ok(idle && self.nodes.iter().all(|node| node.inbox.is_empty()))
+    }
+
+    // Runs rounds until the monitor says to stop, or the network goes idle without one
+    pub fn run(&mut self, max_rounds: Option<u64>) -> Result<NetworkStop, IntcodeError> {
+        let mut rounds = 0;
+
+        loop {
+            if self.nodes.iter().all(|node| node.halted) {
+                return Ok(NetworkStop::AllHalted);
+            }
+            if max_rounds.is_some_and(|max| rounds >= max) {
+                return Ok(NetworkStop::RoundLimit);
+            }
+
+            rounds += 1;
+            if !self.round()? {
+                continue;
+            }
+
+            let action = match &mut self.monitor {
+                Some((_, monitor)) => monitor.idle(),
+                None => return Ok(NetworkStop::Idle)
+            };
+            match action {
+                IdleAction::Inject(packets) if packets.is_empty() => return Ok(NetworkStop::Idle),
+                IdleAction::Inject(packets) => {
+                    for packet in packets {
+                        self.send(packet);
+                    }
+                },
+                IdleAction::Stop => return Ok(NetworkStop::MonitorStopped)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{asm, Limit};
+
+    fn machine(source: &str) -> Computer {
+        Computer::new(asm::assemble(source).unwrap())
+    }
+
+    // Reads a packet whenever one is there and sends it on to 255
+    const FORWARD: &str = "
+                in [address]
+        poll:   in [x]
+                eq [x], #-1, [empty]
+                jt [empty], #poll
+                in [y]
+                out #255
+                out [x]
+                out [y]
+                jt #1, #poll
+        address: .data 0
+        x:      .data 0
+        y:      .data 0
+        empty:  .data 0
+    ";
+
+    // Keeps what reaches 255 and sends the last packet back to 0 whenever the network goes idle,
+    // stopping once it has done so `wakes` times
+    struct Echo {
+        received: Arc<Mutex<Vec<Packet>>>,
+        wakes: usize
+    }
+
+    impl NetworkMonitor for Echo {
+        fn receive(&mut self, packet: Packet) {
+            self.received.lock().unwrap().push(packet);
+        }
+
+        fn idle(&mut self) -> IdleAction {
+            if self.wakes == 0 {
+                return IdleAction::Stop;
+            }
+            self.wakes -= 1;
+
+            let last = self.received.lock().unwrap().last().cloned();
+            let (x, y) = last.map_or((1, 2), |packet| (packet.x + 1, packet.y));
+            IdleAction::Inject(vec![Packet { from: 255, dest: 0, x, y }])
+        }
+    }
+
+    #[test]
+    fn empty_queue_reads_minus_one_once_per_turn() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut network = PacketNetwork::new(vec![machine("
+                in [address]
+                in [x]
+                in [y]
+                out #255
+                out [x]
+                out [y]
+                hlt
+        address: .data 0
+        x:      .data 0
+        y:      .data 0
+        ")]);
+        network.set_monitor(255, Box::new(Echo { received: Arc::clone(&received), wakes: 0 }));
+
+        // The second read of the first turn ends it, the next turn gets another -1
+        assert_eq!(network.run(None), Ok(NetworkStop::AllHalted));
+        assert_eq!(network.rounds(), 2);
+        assert_eq!(*received.lock().unwrap(), vec![Packet { from: 0, dest: 255, x: -1, y: -1 }]);
+    }
+
+    #[test]
+    fn monitor_wakes_the_network_until_it_stops() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let mut network = PacketNetwork::new(vec![machine(FORWARD)]);
+        network.set_monitor(255, Box::new(Echo { received: Arc::clone(&received), wakes: 3 }));
+
+        assert_eq!(network.run(None), Ok(NetworkStop::MonitorStopped));
+        let packets: Vec<(i128, i128)> = received.lock().unwrap().iter().map(|packet| (packet.x, packet.y)).collect();
+        assert_eq!(packets, vec![(1, 2), (2, 2), (3, 2)]);
+
+        // Reading the address, going idle, then a busy and an idle round per wake
+        assert_eq!(network.rounds(), 8);
+    }
+
+    #[test]
+    fn idle_without_a_monitor() {
+        let mut network = PacketNetwork::new(vec![machine(FORWARD), machine(FORWARD)]);
+        assert_eq!(network.run(None), Ok(NetworkStop::Idle));
+        assert_eq!(network.rounds(), 2);
+    }
+
+    #[test]
+    fn packets_to_nobody_are_kept() {
+        let mut network = PacketNetwork::new(vec![machine(FORWARD)]);
+        network.send(Packet { from: 255, dest: 0, x: 7, y: 8 });
+
+        assert_eq!(network.run(None), Ok(NetworkStop::Idle));
+        assert_eq!(network.undeliverable(), &[Packet { from: 0, dest: 255, x: 7, y: 8 }]);
+    }
+
+    #[test]
+    fn round_limit() {
+        // Sends itself a packet every turn, so it never goes idle
+        let mut network = PacketNetwork::new(vec![machine("
+                in [x]
+        loop:   out #0
+                out #1
+                out #2
+                in [x]
+                in [x]
+                jt #1, #loop
+        x:      .data 0
+        ")]);
+
+        assert_eq!(network.run(Some(5)), Ok(NetworkStop::RoundLimit));
+        assert_eq!(network.rounds(), 5);
+    }
+
+    #[test]
+    fn turn_limit_ends_turns_that_never_read() {
+        let mut network = PacketNetwork::new(vec![machine("loop: jt #1, #loop"), machine(FORWARD)]);
+        network.set_turn_limit(100);
+
+        // Busy, not idle, so only the round limit stops it
+        assert_eq!(network.run(Some(3)), Ok(NetworkStop::RoundLimit));
+        assert_eq!(network.machine(0).steps(), 300);
+        assert_eq!(network.machine(0).budget(), None);
+    }
+
+    #[test]
+    fn machine_out_of_its_own_budget_is_an_error() {
+        let mut computer = machine("loop: jt #1, #loop");
+        computer.set_budget(Budget { max_steps: Some(10), ..Budget::default() });
+        let mut network = PacketNetwork::new(vec![computer, machine(FORWARD)]);
+
+        assert_eq!(network.run(None), Err(IntcodeError::BudgetExceeded { address: 0, limit: Limit::Steps }));
+        assert_eq!(network.rounds(), 0);
+    }
+
+    // Wakes the network once with a packet, then has nothing more to send
+    struct Quiet {
+        woken: bool
+    }
+
+    impl NetworkMonitor for Quiet {
+        fn receive(&mut self, _: Packet) {}
+
+        fn idle(&mut self) -> IdleAction {
+            if self.woken {
+                return IdleAction::Inject(Vec::new());
+            }
+            self.woken = true;
+            IdleAction::Inject(vec![Packet { from: 255, dest: 0, x: 1, y: 2 }])
+        }
+    }
+
+    #[test]
+    fn injecting_nothing_ends_the_run() {
+        let mut network = PacketNetwork::new(vec![machine(FORWARD)]);
+        network.set_monitor(255, Box::new(Quiet { woken: false }));
+
+        assert_eq!(network.run(None), Ok(NetworkStop::Idle));
+        assert_eq!(network.rounds(), 4);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::disasm;
+use crate::OpCode;
+
+// Execution counts collected while profiling is on
+#[derive(PartialEq, Debug, Clone)]
+pub struct Profile {
+    pub steps: u64,
+    pub by_address: HashMap<i128, u64>,
+    pub by_op_code: HashMap<OpCode, u64>,
+    pub inputs: u64,
+    pub outputs: u64,
+    pub min_relative_base: i128,
+    pub max_relative_base: i128
+}
+
+impl Profile {
+    pub fn new(relative_base: i128) -> Profile {
+        Profile {
+            steps: 0,
+            by_address: HashMap::new(),
+            by_op_code: HashMap::new(),
+            inputs: 0,
+            outputs: 0,
+            min_relative_base: relative_base,
+            max_relative_base: relative_base
+        }
+    }
+
+    pub(crate) fn record(&mut self, address: i128, op_code: OpCode, relative_base: i128) {
+        self.steps += 1;
+        *self.by_address.entry(address).or_insert(0) += 1;
+        *self.by_op_code.entry(op_code).or_insert(0) += 1;
+
+        match op_code {
+            OpCode::ReadInput => self.inputs += 1,
+            OpCode::PrintAddress => self.outputs += 1,
+            _ => {}
+        }
+
+        self.min_relative_base = self.min_relative_base.min(relative_base);
+        self.max_relative_base = self.max_relative_base.max(relative_base);
+    }
+
+    // Summary plus the top addresses by execution count. Given the program's memory each hot
+    // address is shown with its disassembly.
+    pub fn write_report<O: Write>(&self, out: &mut O, top: usize, memory: Option<&[i128]>) -> io::Result<()> {
+        let percent = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+
+        writeln!(out, "steps: {}", self.steps)?;
+        writeln!(out, "inputs: {}  outputs: {}", self.inputs, self.outputs)?;
+        writeln!(out, "relative base: {} to {}", self.min_relative_base, self.max_relative_base)?;
+
+        let mut op_codes: Vec<(&OpCode, &u64)> = self.by_op_code.iter().collect();
+        op_codes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.mnemonic().cmp(b.0.mnemonic())));
+
+        writeln!(out, "\nby opcode:")?;
+        for (op_code, &count) in op_codes {
+            writeln!(out, "  {:<4} {:>12} {:>6.2}%", op_code.mnemonic(), count, percent(count))?;
+        }
+
+        let mut addresses: Vec<(&i128, &u64)> = self.by_address.iter().collect();
+        addresses.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+        writeln!(out, "\nhot spots:")?;
+        for (&address, &count) in addresses.into_iter().take(top) {
+            let listing = memory.filter(|_| address >= 0)
+                .and_then(|memory| disasm::decode_at(memory, address as usize))
+                .map(|line| line.text)
+                .unwrap_or_default();
+            writeln!(out, "  {:>6} {:>12} {:>6.2}%  {}", address, count, percent(count), listing)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, Computer};
+
+    // Prints 3, 2, 1
+    const LOOP: &str = "
+                arb #7
+        again:  out [n]
+                add [n], #-1, [n]
+                jt [n], #again
+                hlt
+        n:      .data 3
+    ";
+
+    fn profiled(program: &[i128]) -> Profile {
+        let mut computer = Computer::new(program.to_vec());
+        computer.enable_profile();
+        computer.run_to_halt().unwrap();
+        computer.take_profile().unwrap()
+    }
+
+    #[test]
+    fn counts_for_a_loop() {
+        let profile = profiled(&asm::assemble(LOOP).unwrap());
+
+        assert_eq!(profile.steps, 1 + 3 * 3 + 1);
+        assert_eq!(profile.by_address, vec![(0, 1), (2, 3), (4, 3), (8, 3), (11, 1)].into_iter().collect());
+        assert_eq!(profile.by_op_code, vec![
+            (OpCode::SetRelOffset, 1),
+            (OpCode::PrintAddress, 3),
+            (OpCode::Add, 3),
+            (OpCode::JIfTrue, 3),
+            (OpCode::Halt, 1)
+        ].into_iter().collect());
+        assert_eq!((profile.inputs, profile.outputs), (0, 3));
+        assert_eq!((profile.min_relative_base, profile.max_relative_base), (0, 7));
+    }
+
+    #[test]
+    fn report_lists_hot_spots_from_the_given_program() {
+        let program = asm::assemble(LOOP).unwrap();
+        let mut report = Vec::new();
+        profiled(&program).write_report(&mut report, 2, Some(&program)).unwrap();
+        let report = String::from_utf8(report).unwrap();
+
+        assert!(report.starts_with("steps: 11\ninputs: 0  outputs: 3\nrelative base: 0 to 7\n"), "{}", report);
+        assert!(report.ends_with("hot spots:\n       2            3  27.27%  out [12]\n       4            3  27.27%  add [12], #-1, [12]\n"),
+                "{}", report);
+    }
+}
@@ -0,0 +1,264 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::{Budget, Computer, IntcodeError, RunState, Word};
+
+// One setup to try: memory cells to overwrite before running, then input to queue
+#[derive(PartialEq, Debug, Clone)]
+pub struct Candidate<W = i128> {
+    pub patches: Vec<(i128, W)>,
+    pub inputs: Vec<W>
+}
+
+impl<W> Candidate<W> {
+    pub fn patches(patches: Vec<(i128, W)>) -> Candidate<W> {
+        Candidate { patches, inputs: Vec::new() }
+    }
+
+    pub fn inputs(inputs: Vec<W>) -> Candidate<W> {
+        Candidate { patches: Vec::new(), inputs }
+    }
+}
+
+// A finished candidate run. The computer is left as the run did, so memory can be read off it.
+pub struct Outcome<W: Word = i128> {
+    // Position of the candidate in what the generator produced
+    pub index: usize,
+    pub candidate: Candidate<W>,
+    pub state: Result<RunState<W>, IntcodeError>,
+    pub outputs: Vec<W>,
+    pub computer: Computer<W>
+}
+
+// Runs candidates against a program on several threads at once
+pub struct Search<W: Word = i128> {
+    program: Vec<W>,
+    threads: usize,
+    budget: Option<Budget>
+}
+
+impl<W: Word> Search<W> {
+    // Uses as many threads as the machine has cores
+    pub fn new(program: Vec<W>) -> Search<W> {
+        Search {
+            program,
+            threads: thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1),
+            budget: None
+        }
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    // Applied to every candidate on its own, so one that loops forever does not hold up the search
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = Some(budget);
+    }
+
+    pub fn run(&self, index: usize, candidate: Candidate<W>) -> Outcome<W> {
+        let mut computer = Computer::new(self.program.clone());
+        if let Some(budget) = self.budget {
+            computer.set_budget(budget);
+        }
+
+        let mut state = Ok(RunState::Halted);
+        for (pos, value) in candidate.patches.iter() {
+            if let Err(err) = computer.poke(*pos, value.clone()) {
+                state = Err(err);
+            }
+        }
+        for value in candidate.inputs.iter() {
+            computer.push_input(value.clone());
+        }
+        if state.is_ok() {
+            state = computer.run();
+        }
+
+        let mut outputs = Vec::new();
+        while let Some(output) = computer.read_output() {
+            outputs.push(output);
+        }
+
+        Outcome {
+            index,
+            candidate,
+            state,
+            outputs,
+            computer
+        }
+    }
+
+    // The earliest candidate, in generator order, whose outcome matches. Candidates after a match
+    // that is already known are not run.
+    pub fn find_first<I, P>(&self, candidates: I, predicate: P) -> Option<Outcome<W>>
+    where
+        I: IntoIterator<Item = Candidate<W>>,
+        I::IntoIter: Send,
+        P: Fn(&Outcome<W>) -> bool + Sync
+    {
+        let first = AtomicUsize::new(usize::MAX);
+        let found = Mutex::new(None);
+
+        self.each(candidates, |outcome| {
+            if outcome.index < first.load(Ordering::SeqCst) && predicate(&outcome) {
+                let mut found = found.lock().unwrap();
+                if outcome.index < first.load(Ordering::SeqCst) {
+                    first.store(outcome.index, Ordering::SeqCst);
+                    *found = Some(outcome);
+                }
+            }
+        }, |index| index > first.load(Ordering::SeqCst));
+
+        found.into_inner().unwrap()
+    }
+
+    // The candidate with the highest score, the earliest one on ties. Outcomes scored None are
+    // left out, e.g. runs that did not halt.
+    pub fn find_best<I, S, F>(&self, candidates: I, score: F) -> Option<(S, Outcome<W>)>
+    where
+        I: IntoIterator<Item = Candidate<W>>,
+        I::IntoIter: Send,
+        S: Ord + Send,
+        F: Fn(&Outcome<W>) -> Option<S> + Sync
+    {
+        let best: Mutex<Option<(S, Outcome<W>)>> = Mutex::new(None);
+
+        self.each(candidates, |outcome| {
+            if let Some(value) = score(&outcome) {
+                let mut best = best.lock().unwrap();
+                let better = match &*best {
+                    Some((top, top_outcome)) => value > *top || (value == *top && outcome.index < top_outcome.index),
+                    None => true
+                };
+                if better {
+                    *best = Some((value, outcome));
+                }
+            }
+        }, |_| false);
+
+        best.into_inner().unwrap()
+    }
+
+    // Hands candidates out to the worker threads one at a time until they run out. Indexes only
+    // grow, so once one is skipped so is everything after it and the worker can stop.
+    fn each<I, F, S>(&self, candidates: I, found: F, skip: S)
+    where
+        I: IntoIterator<Item = Candidate<W>>,
+        I::IntoIter: Send,
+        F: Fn(Outcome<W>) + Sync,
+        S: Fn(usize) -> bool + Sync
+    {
+        let candidates = Mutex::new(candidates.into_iter().enumerate());
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| loop {
+                    let next = candidates.lock().unwrap().next();
+                    match next {
+                        Some((index, candidate)) if !skip(index) => found(self.run(index, candidate)),
+                        _ => return
+                    }
+                });
+            }
+        });
+    }
+}
+
+// Every ordering of values, e.g. the phase settings in day 7
+pub fn permutations<W: Clone>(values: &[W]) -> Vec<Vec<W>> {
+    if values.len() <= 1 {
+        return vec![values.to_vec()];
+    }
+
+    let mut result = Vec::new();
+    for (i, first) in values.iter().enumerate() {
+        let mut rest = values.to_vec();
+        rest.remove(i);
+        for mut tail in permutations(&rest) {
+            tail.insert(0, first.clone());
+            result.push(tail);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    // Counts the first input down to zero, then prints the second
+    const COUNTDOWN: &str = "
+                in [n]
+                in [x]
+        again:  add [n], #-1, [n]
+                jt [n], #again
+                out [x]
+                hlt
+        n:      .data 0
+        x:      .data 0
+    ";
+
+    fn search() -> Search {
+        let mut search = Search::new(asm::assemble(COUNTDOWN).unwrap());
+        search.set_threads(8);
+        search
+    }
+
+    #[test]
+    fn find_first_returns_the_lowest_index() {
+        // Earlier candidates count down longer, so later matches finish first
+        let candidates = (0..200).map(|x| Candidate::inputs(vec![(200 - x) * 100, x]));
+        let outcome = search().find_first(candidates, |outcome| outcome.outputs[0] % 10 == 7).unwrap();
+
+        assert_eq!(outcome.index, 7);
+        assert_eq!(outcome.candidate.inputs, vec![19300, 7]);
+        assert_eq!(outcome.state, Ok(RunState::Halted));
+    }
+
+    #[test]
+    fn find_first_without_a_match() {
+        let candidates = (0..50).map(|x| Candidate::inputs(vec![1, x]));
+        assert!(search().find_first(candidates, |outcome| outcome.outputs[0] < 0).is_none());
+    }
+
+    #[test]
+    fn find_best_takes_the_earliest_of_equal_scores() {
+        let candidates = (0..100).map(|x| Candidate::inputs(vec![(100 - x) * 100, x]));
+        let (score, outcome) = search().find_best(candidates, |outcome| Some(outcome.outputs[0] % 5)).unwrap();
+
+        assert_eq!(score, 4);
+        assert_eq!(outcome.index, 4);
+    }
+
+    #[test]
+    fn find_best_leaves_out_unscored_outcomes() {
+        let candidates = (0..20).map(|x| Candidate::inputs(vec![1, x]));
+        let best = search().find_best(candidates, |outcome| Some(outcome.outputs[0]).filter(|&x| x < 10));
+        assert_eq!(best.map(|(score, outcome)| (score, outcome.index)), Some((9, 9)));
+    }
+
+    #[test]
+    fn patches_are_applied_before_running() {
+        let outcome = search().run(0, Candidate { patches: vec![(20, 42)], inputs: vec![1, 5] });
+        assert_eq!(outcome.outputs, vec![5]);
+        assert_eq!(outcome.computer.peek(20), 42);
+    }
+
+    #[test]
+    fn permutations_in_order() {
+        assert_eq!(permutations(&[0, 1, 2]), vec![
+            vec![0, 1, 2],
+            vec![0, 2, 1],
+            vec![1, 0, 2],
+            vec![1, 2, 0],
+            vec![2, 0, 1],
+            vec![2, 1, 0]
+        ]);
+        assert_eq!(permutations(&[0, 1, 2, 3, 4]).len(), 120);
+        assert_eq!(permutations::<i128>(&[]), vec![Vec::<i128>::new()]);
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use crate::{Memory, Word};
+
+const HEADER: &str = "intcode-snapshot 1";
+
+// Everything needed to resume a machine where it left off. Watchpoints and traces belong to
+// whoever is looking at the machine, so they are not part of it.
+//
+// On disk it is a small text file, memory written as runs of consecutive cells:
+//
+//     intcode-snapshot 1
+//     ip 12
+//     rb 1000
+//     steps 33
+//     input 5,6
+//     output
+//     mem 0 1102,34463338,34463338,63
+//     mem 1000 1
+#[derive(PartialEq, Debug, Clone)]
+pub struct Snapshot<W: Word = i128> {
+    pub memory: Memory<W>,
+    pub instruction_pointer: i128,
+    pub relative_base: i128,
+    pub steps: u64,
+    pub input: VecDeque<W>,
+    pub output: VecDeque<W>
+}
+
+impl<W: Word> Snapshot<W> {
+    pub fn write_to<O: Write>(&self, out: &mut O) -> io::Result<()> {
+        writeln!(out, "{}", HEADER)?;
+        writeln!(out, "ip {}", self.instruction_pointer)?;
+        writeln!(out, "rb {}", self.relative_base)?;
+        writeln!(out, "steps {}", self.steps)?;
+        writeln!(out, "input {}", join(self.input.iter()))?;
+        writeln!(out, "output {}", join(self.output.iter()))?;
+
+        let cells = self.memory.cells();
+        let mut idx = 0;
+        while idx < cells.len() {
+            let start = cells[idx].0;
+            let mut end = idx + 1;
+            while end < cells.len() && cells[end].0 == start + (end - idx) as i128 {
+                end += 1;
+            }
+
+            writeln!(out, "mem {} {}", start, join(cells[idx..end].iter().map(|(_, value)| value)))?;
+            idx = end;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Snapshot<W>> {
+        let mut lines = reader.lines();
+        match lines.next() {
+            Some(Ok(ref line)) if line.trim() == HEADER => {},
+            Some(Err(err)) => return Err(err),
+            _ => return Err(invalid_data("not an intcode snapshot, or an unknown version".to_string()))
+        }
+
+        let mut snapshot = Snapshot {
+            memory: Memory::default(),
+            instruction_pointer: 0,
+            relative_base: 0,
+            steps: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new()
+        };
+        let mut cells = Vec::new();
+        for line in lines {
+            let line = line?;
+            let mut fields = line.trim().splitn(2, ' ');
+            let key = fields.next().unwrap_or("");
+            let value = fields.next().unwrap_or("").trim();
+
+            match key {
+                "" => {},
+                "ip" => snapshot.instruction_pointer = parse_number(value)?,
+                "rb" => snapshot.relative_base = parse_number(value)?,
+                "steps" => snapshot.steps = parse_number(value)?,
+                "input" => snapshot.input = parse_list(value)?.into_iter().collect(),
+                "output" => snapshot.output = parse_list(value)?.into_iter().collect(),
+                "mem" => {
+                    let mut parts = value.splitn(2, ' ');
+                    let start: i128 = parse_number(parts.next().unwrap_or(""))?;
+                    for (offset, cell) in parse_list(parts.next().unwrap_or(""))?.into_iter().enumerate() {
+                        cells.push((start + offset as i128, cell));
+                    }
+                },
+                _ => return Err(invalid_data(format!("unknown snapshot field '{}'", key)))
+            }
+        }
+
+        snapshot.memory = Memory::from_cells(cells);
+        Ok(snapshot)
+    }
+}
+
+fn join<'a, W: Word, I: Iterator<Item = &'a W>>(values: I) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn parse_number<T: FromStr>(text: &str) -> io::Result<T> {
+    text.trim().parse::<T>().map_err(|_| invalid_number(text))
+}
+
+fn parse_list<W: Word>(text: &str) -> io::Result<Vec<W>> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    text.split(',').map(parse_number).collect()
+}
+
+fn invalid_number(text: &str) -> io::Error {
+    invalid_data(format!("bad number '{}'", text))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{asm, Computer, RunState};
+
+    // Echoes every input, keeping each one further up in memory
+    const ECHO: &str = "
+                arb #1000000
+        again:  in rb+0
+                out rb+0
+                arb #1
+                jt #1, #again
+    ";
+
+    fn outputs(computer: &mut Computer) -> Vec<i128> {
+        let mut outputs = Vec::new();
+        while let Some(output) = computer.read_output() {
+            outputs.push(output);
+        }
+        outputs
+    }
+
+    #[test]
+    fn roundtrip_of_a_paused_machine() {
+        let mut computer = Computer::new(asm::assemble(ECHO).unwrap());
+        for input in 1..=3 {
+            computer.push_input(input);
+        }
+        // Stopped after the second input was read, before it was printed
+        for _ in 0..6 {
+            computer.step().unwrap();
+        }
+
+        let snapshot = computer.snapshot();
+        assert_eq!(snapshot.relative_base, 1000001);
+        assert_eq!(snapshot.input, VecDeque::from(vec![3]));
+        assert_eq!(snapshot.output, VecDeque::from(vec![1]));
+
+        let mut text = Vec::new();
+        snapshot.write_to(&mut text).unwrap();
+        let restored = Snapshot::read_from(&text[..]).unwrap();
+        assert_eq!(restored, snapshot);
+
+        let mut restored = Computer::from_snapshot(restored);
+        assert_eq!((restored.steps(), restored.instruction_pointer()), (computer.steps(), computer.instruction_pointer()));
+        assert_eq!(restored.peek(1000001), 2);
+
+        for machine in [&mut computer, &mut restored].iter_mut() {
+            machine.push_input(4);
+            assert_eq!(machine.run(), Ok(RunState::NeedsInput));
+        }
+        assert_eq!(outputs(&mut restored), vec![1, 2, 3, 4]);
+        assert_eq!(outputs(&mut computer), vec![1, 2, 3, 4]);
+        assert_eq!(restored.snapshot(), computer.snapshot());
+    }
+
+    #[test]
+    fn high_pages_are_written_as_their_own_runs() {
+        let mut computer = Computer::new(vec![99]);
+        computer.poke(5000000, 7).unwrap();
+
+        let mut text = Vec::new();
+        computer.snapshot().write_to(&mut text).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("\nmem 0 99\n"), "{}", text);
+        assert!(text.contains("\nmem 5000000 7\n"), "{}", text);
+    }
+
+    #[test]
+    fn bad_header_or_version() {
+        for text in ["", "intcode-snapshot 2\nip 0\n", "ip 0\n", "intcode-trace 1\n"].iter() {
+            let err = Snapshot::<i128>::read_from(text.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn bad_fields() {
+        for text in ["intcode-snapshot 1\nspeed 5\n", "intcode-snapshot 1\nip x\n", "intcode-snapshot 1\nmem 0 1,,2\n"].iter() {
+            let err = Snapshot::<i128>::read_from(text.as_bytes()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,623 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::search::{Candidate, Search};
+use crate::{Budget, Computer, OpCode, ParamModes, RunState};
+
+// Finds values for some unknown memory cells or inputs that make the program produce a target,
+// e.g. the noun and verb in day 2. The program is run once with the unknowns as variables, which
+// works as long as nothing it does depends on them: no branches on them, no writes or jumps to
+// addresses computed from them. The target then comes out as a polynomial in the unknowns, and
+// solving that only needs arithmetic. Otherwise it falls back to running every combination.
+pub struct Solver {
+    program: Vec<i128>,
+    variables: Vec<Variable>,
+    inputs: Vec<InputSlot>,
+    max_steps: u64
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Variable {
+    pub name: String,
+    pub range: RangeInclusive<i128>,
+    // The memory cell it is patched into, None for inputs
+    pub position: Option<i128>
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+enum InputSlot {
+    Known(i128),
+    Variable(usize)
+}
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Target {
+    // A memory cell once the program halts
+    Memory(i128),
+    // The nth value printed, counting from 0
+    Output(usize)
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Method {
+    // The target as a polynomial in the variables
+    Symbolic(Polynomial),
+    // Why solving symbolically was not possible
+    Search(String)
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Solution {
+    // In the order the variables were added
+    pub values: Vec<i128>,
+    pub method: Method
+}
+
+impl Solver {
+    pub fn new(program: Vec<i128>) -> Solver {
+        Solver {
+            program,
+            variables: Vec::new(),
+            inputs: Vec::new(),
+            max_steps: 1_000_000
+        }
+    }
+
+    pub fn unknown_cell(&mut self, name: &str, position: i128, range: RangeInclusive<i128>) {
+        self.variables.push(Variable { name: name.to_string(), range, position: Some(position) });
+    }
+
+    // Inputs are read in the order they were added, known and unknown alike
+    pub fn unknown_input(&mut self, name: &str, range: RangeInclusive<i128>) {
+        self.inputs.push(InputSlot::Variable(self.variables.len()));
+        self.variables.push(Variable { name: name.to_string(), range, position: None });
+    }
+
+    pub fn input(&mut self, value: i128) {
+        self.inputs.push(InputSlot::Known(value));
+    }
+
+    // Per run, symbolic or not. Runs that go over it are taken as not reaching the target.
+    pub fn set_max_steps(&mut self, max_steps: u64) {
+        self.max_steps = max_steps;
+    }
+
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    // Runs with the unknowns as variables and returns the target in terms of them, or why that
+    // did not work out
+    pub fn symbolic(&self, target: Target) -> Result<Polynomial, String> {
+        let mut machine = SymbolicMachine::new(self);
+        machine.run()?;
+
+        let value = match target {
+            Target::Memory(pos) => machine.read(pos),
+            Target::Output(index) => machine.outputs.get(index).cloned()
+                .ok_or_else(|| format!("the program printed only {} values", machine.outputs.len()))?
+        };
+        value.ok_or_else(|| "the target is not a polynomial in the unknowns, e.g. it went through a comparison".to_string())
+    }
+
+    pub fn solve(&self, target: Target, goal: i128) -> Option<Solution> {
+        let reason = match self.symbolic(target) {
+            Ok(polynomial) => {
+                // Checked with a real run in case the path taken was not the same for every value
+                match self.solve_polynomial(&polynomial, goal) {
+                    Ok(Some(values)) if self.reaches(target, goal, &values) => {
+                        return Some(Solution { values, method: Method::Symbolic(polynomial) });
+                    },
+                    Ok(Some(_)) => "the symbolic solution does not hold when run".to_string(),
+                    Ok(None) => return None,
+                    Err(reason) => reason
+                }
+            },
+            Err(reason) => reason
+        };
+
+        self.search(target, goal).map(|values| Solution { values, method: Method::Search(reason) })
+    }
+
+    // Picks a variable the polynomial is linear in and solves for it exactly for every combination
+    // of the others. Without one every combination is evaluated, which is still only arithmetic.
+    // Combinations that overflow are skipped, and if nothing else matched the caller has to search.
+    fn solve_polynomial(&self, polynomial: &Polynomial, goal: i128) -> Result<Option<Vec<i128>>, String> {
+        let used: Vec<usize> = (0..self.variables.len()).filter(|&var| polynomial.degree(var) > 0).collect();
+        let linear = used.iter().copied().find(|&var| polynomial.degree(var) == 1);
+        let enumerated: Vec<usize> = used.iter().copied().filter(|&var| Some(var) != linear).collect();
+
+        let mut values: Vec<i128> = self.variables.iter().map(|variable| *variable.range.start()).collect();
+        let combinations = Combinations::new(enumerated.iter().map(|&var| self.variables[var].range.clone()).collect());
+        let mut overflowed = false;
+
+        for combination in combinations {
+            for (&var, &value) in enumerated.iter().zip(combination.iter()) {
+                values[var] = value;
+            }
+
+            match linear {
+                Some(var) => {
+                    // goal = a * var + b, with everything else fixed
+                    values[var] = 0;
+                    let b = polynomial.evaluate(&values);
+                    values[var] = 1;
+                    let a = polynomial.evaluate(&values).zip(b).and_then(|(a, b)| a.checked_sub(b));
+                    let rest = b.and_then(|b| goal.checked_sub(b));
+                    let (a, rest) = match (a, rest) {
+                        (Some(a), Some(rest)) => (a, rest),
+                        _ => {
+                            overflowed = true;
+                            continue;
+                        }
+                    };
+
+                    let range = &self.variables[var].range;
+                    if a == 0 && rest == 0 && !range.is_empty() {
+                        // The goal holds whatever the variable is
+                        values[var] = *range.start();
+                        return Ok(Some(values));
+                    }
+                    if a != 0 {
+                        // Only i128::MIN / -1 has no answer, and that one is too big for the range anyway
+                        let value = rest.checked_div(a).filter(|_| rest.checked_rem(a) == Some(0));
+                        if let Some(value) = value.filter(|value| range.contains(value)) {
+                            values[var] = value;
+                            return Ok(Some(values));
+                        }
+                    }
+                },
+                None => match polynomial.evaluate(&values) {
+                    Some(value) if value == goal => return Ok(Some(values)),
+                    Some(_) => {},
+                    None => overflowed = true
+                }
+            }
+        }
+
+        if overflowed {
+            Err("the polynomial overflows for some values".to_string())
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn search(&self, target: Target, goal: i128) -> Option<Vec<i128>> {
+        let mut search = Search::new(self.program.clone());
+        search.set_budget(Budget { max_steps: Some(self.max_steps), ..Budget::default() });
+
+        let ranges = self.variables.iter().map(|variable| variable.range.clone()).collect();
+        let candidates = Combinations::new(ranges).map(|values| self.candidate(&values));
+
+        let found = search.find_first(candidates, |outcome| {
+            outcome.state == Ok(RunState::Halted) && target_value(target, &outcome.computer, &outcome.outputs) == Some(goal)
+        })?;
+        Some(self.values_of(&found.candidate))
+    }
+
+    fn reaches(&self, target: Target, goal: i128, values: &[i128]) -> bool {
+        let mut search = Search::new(self.program.clone());
+        search.set_budget(Budget { max_steps: Some(self.max_steps), ..Budget::default() });
+
+        let outcome = search.run(0, self.candidate(values));
+        outcome.state == Ok(RunState::Halted) && target_value(target, &outcome.computer, &outcome.outputs) == Some(goal)
+    }
+
+    fn candidate(&self, values: &[i128]) -> Candidate {
+        let patches = self.variables.iter()
+            .zip(values)
+            .filter_map(|(variable, &value)| variable.position.map(|pos| (pos, value)))
+            .collect();
+        let inputs = self.inputs.iter()
+            .map(|slot| match *slot {
+                InputSlot::Known(value) => value,
+                InputSlot::Variable(var) => values[var]
+            })
+            .collect();
+
+        Candidate { patches, inputs }
+    }
+
+    // Reads the variable values back out of a candidate built by candidate()
+    fn values_of(&self, candidate: &Candidate) -> Vec<i128> {
+        let mut values = vec![0; self.variables.len()];
+        let mut patches = candidate.patches.iter();
+        for (var, variable) in self.variables.iter().enumerate() {
+            if variable.position.is_some() {
+                values[var] = patches.next().expect("a patch per memory variable").1;
+            }
+        }
+        for (slot, &value) in self.inputs.iter().zip(candidate.inputs.iter()) {
+            if let InputSlot::Variable(var) = *slot {
+                values[var] = value;
+            }
+        }
+
+        values
+    }
+}
+
+fn target_value(target: Target, computer: &Computer, outputs: &[i128]) -> Option<i128> {
+    match target {
+        Target::Memory(pos) => Some(computer.peek(pos)),
+        Target::Output(index) => outputs.get(index).copied()
+    }
+}
+
+// Every combination of values from the ranges, the last one changing fastest
+struct Combinations {
+    ranges: Vec<RangeInclusive<i128>>,
+    current: Option<Vec<i128>>
+}
+
+impl Combinations {
+    fn new(ranges: Vec<RangeInclusive<i128>>) -> Combinations {
+        let current = if ranges.iter().any(|range| range.is_empty()) {
+            None
+        } else {
+            Some(ranges.iter().map(|range| *range.start()).collect())
+        };
+
+        Combinations { ranges, current }
+    }
+}
+
+impl Iterator for Combinations {
+    type Item = Vec<i128>;
+
+    fn next(&mut self) -> Option<Vec<i128>> {
+        let combination = self.current.clone()?;
+
+        let mut next = combination.clone();
+        let mut carry = true;
+        for (value, range) in next.iter_mut().zip(self.ranges.iter()).rev() {
+            if *value < *range.end() {
+                *value += 1;
+                carry = false;
+                break;
+            }
+            *value = *range.start();
+        }
+        self.current = if carry { None } else { Some(next) };
+
+        Some(combination)
+    }
+}
+
+// Integer polynomial over the solver's variables. Each term maps the exponent of every variable
+// to its coefficient.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Polynomial {
+    terms: BTreeMap<Vec<u32>, i128>,
+    names: Vec<String>
+}
+
+impl Polynomial {
+    fn constant(names: &[String], value: i128) -> Polynomial {
+        let mut terms = BTreeMap::new();
+        if value != 0 {
+            terms.insert(vec![0; names.len()], value);
+        }
+
+        Polynomial { terms, names: names.to_vec() }
+    }
+
+    fn variable(names: &[String], var: usize) -> Polynomial {
+        let mut exponents = vec![0; names.len()];
+        exponents[var] = 1;
+
+        let mut terms = BTreeMap::new();
+        terms.insert(exponents, 1);
+        Polynomial { terms, names: names.to_vec() }
+    }
+
+    pub fn as_constant(&self) -> Option<i128> {
+        match self.terms.len() {
+            0 => Some(0),
+            1 => self.terms.get(&vec![0; self.names.len()]).copied(),
+            _ => None
+        }
+    }
+
+    pub fn degree(&self, var: usize) -> u32 {
+        self.terms.keys().map(|exponents| exponents[var]).max().unwrap_or(0)
+    }
+
+    // None on overflow
+    pub fn evaluate(&self, values: &[i128]) -> Option<i128> {
+        let mut total: i128 = 0;
+        for (exponents, &coefficient) in self.terms.iter() {
+            let mut term = coefficient;
+            for (&value, &exponent) in values.iter().zip(exponents.iter()) {
+                term = term.checked_mul(value.checked_pow(exponent)?)?;
+            }
+            total = total.checked_add(term)?;
+        }
+
+        Some(total)
+    }
+
+    fn add(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut terms = self.terms.clone();
+        for (exponents, &coefficient) in other.terms.iter() {
+            let sum = terms.get(exponents).copied().unwrap_or(0).checked_add(coefficient)?;
+            if sum == 0 {
+                terms.remove(exponents);
+            } else {
+                terms.insert(exponents.clone(), sum);
+            }
+        }
+
+        Some(Polynomial { terms, ..self.clone() })
+    }
+
+    fn mul(&self, other: &Polynomial) -> Option<Polynomial> {
+        let mut product = Polynomial { terms: BTreeMap::new(), ..self.clone() };
+        for (left, &a) in self.terms.iter() {
+            for (right, &b) in other.terms.iter() {
+                let exponents = left.iter().zip(right.iter()).map(|(x, y)| x + y).collect();
+                let mut term = BTreeMap::new();
+                term.insert(exponents, a.checked_mul(b)?);
+                product = product.add(&Polynomial { terms: term, ..self.clone() })?;
+            }
+        }
+
+        Some(product)
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+
+        // Highest degree first
+        for (i, (exponents, &coefficient)) in self.terms.iter().rev().enumerate() {
+            let factors: Vec<String> = exponents.iter()
+                .enumerate()
+                .filter(|&(_, &exponent)| exponent > 0)
+                .map(|(var, &exponent)| match exponent {
+                    1 => self.names[var].clone(),
+                    _ => format!("{}^{}", self.names[var], exponent)
+                })
+                .collect();
+
+            let sign = if coefficient < 0 { "-" } else { "+" };
+            if i == 0 {
+                write!(f, "{}", if coefficient < 0 { "-" } else { "" })?;
+            } else {
+                write!(f, " {} ", sign)?;
+            }
+
+            let magnitude = coefficient.unsigned_abs();
+            match (magnitude, factors.is_empty()) {
+                (_, true) => write!(f, "{}", magnitude)?,
+                (1, false) => write!(f, "{}", factors.join("*"))?,
+                (_, false) => write!(f, "{}*{}", magnitude, factors.join("*"))?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Just enough of an Intcode machine to run over polynomials. None in a cell means a value that
+// cannot be expressed, like one read from an address that depends on an unknown. That is fine
+// until something needs it.
+struct SymbolicMachine<'a> {
+    solver: &'a Solver,
+    names: Vec<String>,
+    memory: HashMap<i128, Option<Polynomial>>,
+    instruction_pointer: i128,
+    relative_base: i128,
+    inputs: std::slice::Iter<'a, InputSlot>,
+    outputs: Vec<Option<Polynomial>>
+}
+
+impl<'a> SymbolicMachine<'a> {
+    fn new(solver: &'a Solver) -> SymbolicMachine<'a> {
+        let names: Vec<String> = solver.variables.iter().map(|variable| variable.name.clone()).collect();
+        let mut memory: HashMap<i128, Option<Polynomial>> = solver.program.iter()
+            .enumerate()
+            .map(|(pos, &value)| (pos as i128, Some(Polynomial::constant(&names, value))))
+            .collect();
+        for (var, variable) in solver.variables.iter().enumerate() {
+            if let Some(pos) = variable.position {
+                memory.insert(pos, Some(Polynomial::variable(&names, var)));
+            }
+        }
+
+        SymbolicMachine {
+            solver,
+            names,
+            memory,
+            instruction_pointer: 0,
+            relative_base: 0,
+            inputs: solver.inputs.iter(),
+            outputs: Vec::new()
+        }
+    }
+
+    fn read(&self, pos: i128) -> Option<Polynomial> {
+        match self.memory.get(&pos) {
+            Some(value) => value.clone(),
+            None => Some(Polynomial::constant(&self.names, 0))
+        }
+    }
+
+    // Values that steer the machine have to be known
+    fn known(&self, value: Option<Polynomial>, what: &str) -> Result<i128, String> {
+        value.as_ref()
+            .and_then(|value| value.as_constant())
+            .ok_or_else(|| format!("{} at {} depends on an unknown", what, self.instruction_pointer))
+    }
+
+    // The address a parameter refers to, None for immediates
+    fn address(&self, offset: i128, mode: ParamModes) -> Option<i128> {
+        let raw = self.read(self.instruction_pointer + offset)?.as_constant()?;
+        match mode {
+            ParamModes::ImmediateMode => None,
+            ParamModes::PositionMode => Some(raw),
+            ParamModes::RelativeMode => Some(raw + self.relative_base)
+        }
+    }
+
+    fn param(&self, offset: i128, mode: ParamModes) -> Option<Polynomial> {
+        match mode {
+            ParamModes::ImmediateMode => self.read(self.instruction_pointer + offset),
+            _ => self.read(self.address(offset, mode)?)
+        }
+    }
+
+    fn store(&mut self, offset: i128, mode: ParamModes, value: Option<Polynomial>) -> Result<(), String> {
+        let pos = self.address(offset, mode)
+            .ok_or_else(|| format!("a write at {} goes to an address that depends on an unknown", self.instruction_pointer))?;
+        if pos < 0 {
+            return Err(format!("a write at {} goes to negative address {}", self.instruction_pointer, pos));
+        }
+
+        self.memory.insert(pos, value);
+        Ok(())
+    }
+
+    fn run(&mut self) -> Result<(), String> {
+        for _ in 0..self.solver.max_steps {
+            let code = self.known(self.read(self.instruction_pointer), "the instruction")?;
+            let instruction = Computer::get_instruction(self.instruction_pointer, code).map_err(|err| err.to_string())?;
+            let modes = &instruction.param_modes;
+
+            match instruction.op_code {
+                OpCode::Add | OpCode::Multiply | OpCode::Lt | OpCode::Eq => {
+                    let left = self.param(1, modes[0]);
+                    let right = self.param(2, modes[1]);
+                    let result = match (left, right) {
+                        (Some(left), Some(right)) => match instruction.op_code {
+                            OpCode::Add => left.add(&right),
+                            OpCode::Multiply => left.mul(&right),
+                            // Comparisons of unknowns are fine as long as nothing branches on them
+                            _ => match (left.as_constant(), right.as_constant()) {
+                                (Some(a), Some(b)) if instruction.op_code == OpCode::Lt => Some(Polynomial::constant(&self.names, (a < b) as i128)),
+                                (Some(a), Some(b)) => Some(Polynomial::constant(&self.names, (a == b) as i128)),
+                                _ => None
+                            }
+                        },
+                        _ => None
+                    };
+                    self.store(3, modes[2], result)?;
+                    self.instruction_pointer += 4;
+                },
+                OpCode::ReadInput => {
+                    let value = match self.inputs.next() {
+                        Some(InputSlot::Known(value)) => Polynomial::constant(&self.names, *value),
+                        Some(InputSlot::Variable(var)) => Polynomial::variable(&self.names, *var),
+                        None => return Err("the program asked for more input than was given".to_string())
+                    };
+                    self.store(1, modes[0], Some(value))?;
+                    self.instruction_pointer += 2;
+                },
+                OpCode::PrintAddress => {
+                    let value = self.param(1, modes[0]);
+                    self.outputs.push(value);
+                    self.instruction_pointer += 2;
+                },
+                OpCode::JIfTrue | OpCode::JIfFalse => {
+                    let condition = self.param(1, modes[0]);
+                    let condition = self.known(condition, "a branch")?;
+                    if (condition != 0) == (instruction.op_code == OpCode::JIfTrue) {
+                        let target = self.param(2, modes[1]);
+                        self.instruction_pointer = self.known(target, "a jump target")?;
+                    } else {
+                        self.instruction_pointer += 3;
+                    }
+                },
+                OpCode::SetRelOffset => {
+                    let offset = self.param(1, modes[0]);
+                    self.relative_base += self.known(offset, "the relative base")?;
+                    self.instruction_pointer += 2;
+                },
+                OpCode::Halt => return Ok(())
+            }
+        }
+
+        Err(format!("the program ran for more than {} steps", self.solver.max_steps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn day_2_noun_and_verb() {
+        let input = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("../day_2/p2/input")).unwrap();
+        let mut solver = Solver::new(crate::parse_program(&input).unwrap());
+        solver.unknown_cell("noun", 1, 0..=99);
+        solver.unknown_cell("verb", 2, 0..=99);
+
+        let solution = solver.solve(Target::Memory(0), 19690720).unwrap();
+        assert_eq!(solution.values, vec![45, 59]);
+        match solution.method {
+            Method::Symbolic(polynomial) => assert_eq!(polynomial.to_string(), "432000*noun + verb + 250661"),
+            method => panic!("solved by {:?}", method)
+        }
+    }
+
+    #[test]
+    fn branch_on_an_unknown_falls_back_to_search() {
+        // Prints whether the input equals 8
+        let mut solver = Solver::new(vec![3, 9, 8, 9, 10, 9, 4, 9, 99, -1, 8]);
+        solver.unknown_input("x", -20..=20);
+
+        let solution = solver.solve(Target::Output(0), 1).unwrap();
+        assert_eq!(solution.values, vec![8]);
+        assert!(matches!(solution.method, Method::Search(_)));
+    }
+
+    #[test]
+    fn goal_that_does_not_depend_on_the_linear_variable() {
+        // [0] = x * y with y only ever 0
+        let mut solver = Solver::new(vec![2, 5, 6, 0, 99, 0, 0]);
+        solver.unknown_cell("x", 5, 1..=3);
+        solver.unknown_cell("y", 6, 0..=0);
+
+        let solution = solver.solve(Target::Memory(0), 0).unwrap();
+        assert_eq!(solution.values, vec![1, 0]);
+        assert!(matches!(solution.method, Method::Symbolic(_)));
+    }
+
+    #[test]
+    fn no_solution() {
+        let mut solver = Solver::new(vec![1, 5, 6, 0, 99, 0, 0]);
+        solver.unknown_cell("x", 5, 0..=9);
+        solver.unknown_cell("y", 6, 0..=9);
+
+        assert_eq!(solver.solve(Target::Memory(0), 19), None);
+        assert_eq!(solver.solve(Target::Memory(0), 18).map(|solution| solution.values), Some(vec![9, 9]));
+    }
+
+    #[test]
+    fn overflowing_combinations_are_skipped() {
+        // [0] = x * x * 2^100, which only fits while |x| < 2^14
+        let mut solver = Solver::new(vec![2, 9, 9, 0, 2, 0, 10, 0, 99, 0, 1 << 100]);
+        solver.unknown_cell("x", 9, -(1 << 14)..=10);
+
+        let solution = solver.solve(Target::Memory(0), 9 << 100).unwrap();
+        assert_eq!(solution.values, vec![-3]);
+        assert!(matches!(solution.method, Method::Symbolic(_)));
+    }
+
+    #[test]
+    fn dividing_the_smallest_value_by_minus_one() {
+        // [0] = -x
+        let mut solver = Solver::new(vec![1002, 5, -1, 0, 99, 0]);
+        solver.unknown_cell("x", 5, 0..=9);
+
+        assert_eq!(solver.solve(Target::Memory(0), i128::MIN), None);
+        assert_eq!(solver.solve(Target::Memory(0), -7).map(|solution| solution.values), Some(vec![7]));
+    }
+}
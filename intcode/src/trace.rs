@@ -0,0 +1,339 @@
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Read, Write};
+
+use crate::{Computer, OpCode, ParamModes, Word};
+
+// Binary traces start with this, followed by a format version byte
+const MAGIC: &[u8; 4] = b"ICTR";
+const VERSION: u8 = 1;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum TraceFormat {
+    // {"step":0,"ip":0,"op":"mul","modes":[1,1,0],"operands":[3,4,63],"writes":[[63,12]],"rb":0}
+    JsonLines,
+    // Header then one record after the other, all integers little endian:
+    //   step u64, ip i128, opcode u8, mode count u8, modes u8 each, operand count u8, operands i128 each,
+    //   write count u8, writes (position i128, value i128) each, rb i128
+    Binary
+}
+
+// One executed instruction. Operands are resolved: the value read for input parameters and the
+// position written to for the output parameter. rb is the relative base before the instruction ran.
+#[derive(PartialEq, Debug, Clone)]
+pub struct TraceRecord<W = i128> {
+    pub step: u64,
+    pub ip: i128,
+    pub op_code: OpCode,
+    pub modes: Vec<ParamModes>,
+    pub operands: Vec<W>,
+    pub writes: Vec<(i128, W)>,
+    pub rb: i128
+}
+
+impl<W: Word> TraceRecord<W> {
+    pub fn to_json(&self) -> String {
+        let modes: Vec<String> = self.modes.iter().map(|&mode| (mode as u8).to_string()).collect();
+        let operands: Vec<String> = self.operands.iter().map(|value| value.to_string()).collect();
+        let writes: Vec<String> = self.writes.iter().map(|(pos, value)| format!("[{},{}]", pos, value)).collect();
+
+        format!("{{\"step\":{},\"ip\":{},\"op\":\"{}\",\"modes\":[{}],\"operands\":[{}],\"writes\":[{}],\"rb\":{}}}",
+                self.step, self.ip, self.op_code.mnemonic(), modes.join(","), operands.join(","), writes.join(","), self.rb)
+    }
+
+    // Values are stored as i128, so words wider than that cannot be written in this format
+    pub fn write_binary<O: Write + ?Sized>(&self, out: &mut O) -> io::Result<()> {
+        out.write_all(&self.step.to_le_bytes())?;
+        out.write_all(&self.ip.to_le_bytes())?;
+        out.write_all(&[self.op_code as u8, self.modes.len() as u8])?;
+        for &mode in self.modes.iter() {
+            out.write_all(&[mode as u8])?;
+        }
+
+        out.write_all(&[self.operands.len() as u8])?;
+        for operand in self.operands.iter() {
+            out.write_all(&narrow(operand)?.to_le_bytes())?;
+        }
+
+        out.write_all(&[self.writes.len() as u8])?;
+        for (pos, value) in self.writes.iter() {
+            out.write_all(&pos.to_le_bytes())?;
+            out.write_all(&narrow(value)?.to_le_bytes())?;
+        }
+
+        out.write_all(&self.rb.to_le_bytes())
+    }
+}
+
+// Held by the computer while tracing. Write errors are kept until the trace is finished so that
+// running the program does not need to know about them.
+pub(crate) struct Tracer<W> {
+    out: Box<dyn Write + Send>,
+    format: TraceFormat,
+    error: Option<io::Error>,
+    pub(crate) pending: Option<TraceRecord<W>>
+}
+
+impl<W: Word> Tracer<W> {
+    pub(crate) fn new(mut out: Box<dyn Write + Send>, format: TraceFormat) -> Tracer<W> {
+        let mut error = None;
+        if format == TraceFormat::Binary {
+            error = out.write_all(MAGIC).and_then(|_| out.write_all(&[VERSION])).err();
+        }
+
+        Tracer {
+            out,
+            format,
+            error,
+            pending: None
+        }
+    }
+
+    pub(crate) fn record(&mut self, record: &TraceRecord<W>) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let result = match self.format {
+            TraceFormat::JsonLines => writeln!(self.out, "{}", record.to_json()),
+            TraceFormat::Binary => record.write_binary(&mut self.out)
+        };
+        self.error = result.err();
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => self.out.flush()
+        }
+    }
+}
+
+// Reads back a whole binary trace
+pub fn read_binary_trace<R: Read>(mut reader: R) -> io::Result<Vec<TraceRecord>> {
+    let mut header = [0; 5];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC || header[4] != VERSION {
+        return Err(invalid_data("not an intcode trace, or an unknown version"));
+    }
+
+    let mut records = Vec::new();
+    while let Some(step) = read_step(&mut reader)? {
+        let ip = read_i128(&mut reader)?;
+        let op_code = Computer::get_instruction(ip, read_u8(&mut reader)? as i128)
+            .map_err(|err| invalid_data(&err.to_string()))?
+            .op_code;
+
+        let mut modes = Vec::new();
+        for _ in 0..read_u8(&mut reader)? {
+            let mode = read_u8(&mut reader)?;
+            modes.push(param_mode(mode).ok_or_else(|| invalid_data(&format!("bad parameter mode {}", mode)))?);
+        }
+
+        let mut operands = Vec::new();
+        for _ in 0..read_u8(&mut reader)? {
+            operands.push(read_i128(&mut reader)?);
+        }
+
+        let mut writes = Vec::new();
+        for _ in 0..read_u8(&mut reader)? {
+            writes.push((read_i128(&mut reader)?, read_i128(&mut reader)?));
+        }
+
+        records.push(TraceRecord {
+            step,
+            ip,
+            op_code,
+            modes,
+            operands,
+            writes,
+            rb: read_i128(&mut reader)?
+        });
+    }
+
+    Ok(records)
+}
+
+// Reads back a whole JSON Lines trace. Only takes the exact layout to_json writes.
+pub fn read_json_trace<R: BufRead>(reader: R) -> io::Result<Vec<TraceRecord>> {
+    let mut records = Vec::new();
+    for (idx, line) in reader.lines().enumerate() {
+        let record = parse_json(&line?).ok_or_else(|| invalid_data(&format!("line {}: not a trace record", idx + 1)))?;
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+fn parse_json(line: &str) -> Option<TraceRecord> {
+    let rest = line.trim().strip_prefix("{\"step\":")?;
+    let (step, rest) = rest.split_once(",\"ip\":")?;
+    let (ip, rest) = rest.split_once(",\"op\":\"")?;
+    let (op, rest) = rest.split_once("\",\"modes\":[")?;
+    let (modes, rest) = rest.split_once("],\"operands\":[")?;
+    let (operands, rest) = rest.split_once("],\"writes\":[")?;
+    let (writes, rest) = rest.split_once("],\"rb\":")?;
+    let rb = rest.strip_suffix('}')?;
+
+    // Writes are pairs, [[63,12],[64,1]] with the outer brackets already gone
+    let writes = match writes.strip_prefix('[').and_then(|writes| writes.strip_suffix(']')) {
+        Some(writes) => writes.split("],[")
+            .map(|pair| {
+                let (pos, value) = pair.split_once(',')?;
+                Some((pos.parse().ok()?, value.parse().ok()?))
+            })
+            .collect::<Option<Vec<_>>>()?,
+        None if writes.is_empty() => Vec::new(),
+        None => return None
+    };
+
+    let modes = split_list(modes)?.into_iter()
+        .map(|mode| param_mode(u8::try_from(mode).ok()?))
+        .collect::<Option<_>>()?;
+
+    Some(TraceRecord {
+        step: step.parse().ok()?,
+        ip: ip.parse().ok()?,
+        op_code: OpCode::from_mnemonic(op)?,
+        modes,
+        operands: split_list(operands)?,
+        writes,
+        rb: rb.parse().ok()?
+    })
+}
+
+fn split_list(list: &str) -> Option<Vec<i128>> {
+    if list.is_empty() {
+        return Some(Vec::new());
+    }
+    list.split(',').map(|value| value.parse().ok()).collect()
+}
+
+fn param_mode(mode: u8) -> Option<ParamModes> {
+    match mode {
+        0 => Some(ParamModes::PositionMode),
+        1 => Some(ParamModes::ImmediateMode),
+        2 => Some(ParamModes::RelativeMode),
+        _ => None
+    }
+}
+
+// None where the trace ends cleanly. Running out part way into a record is an error.
+fn read_step<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut buffer = [0; 8];
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "trace ends part way into a record")),
+            Ok(count) => filled += count,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(err)
+        }
+    }
+
+    Ok(Some(u64::from_le_bytes(buffer)))
+}
+
+fn narrow<W: Word>(value: &W) -> io::Result<i128> {
+    value.to_i128().ok_or_else(|| invalid_data(&format!("{} does not fit in a binary trace", value)))
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    let mut buffer = [0; 1];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer[0])
+}
+
+fn read_i128<R: Read>(reader: &mut R) -> io::Result<i128> {
+    let mut buffer = [0; 16];
+    reader.read_exact(&mut buffer)?;
+    Ok(i128::from_le_bytes(buffer))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // Keeps what the computer writes so the test can read it back after the run
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // arb #20, add #2, #3, rb+0, out rb+0, hlt
+    fn traced(format: TraceFormat) -> Vec<u8> {
+        let out = Shared::default();
+        let mut computer = Computer::new(vec![109, 20, 21101, 2, 3, 0, 204, 0, 99]);
+        computer.trace_to(out.clone(), format);
+        computer.run_to_halt().unwrap();
+        computer.finish_trace().unwrap();
+
+        let bytes = out.0.lock().unwrap().clone();
+        bytes
+    }
+
+    fn expected() -> Vec<TraceRecord> {
+        use ParamModes::*;
+
+        vec![
+            TraceRecord { step: 0, ip: 0, op_code: OpCode::SetRelOffset, modes: vec![ImmediateMode], operands: vec![20], writes: vec![], rb: 0 },
+            TraceRecord { step: 1, ip: 2, op_code: OpCode::Add, modes: vec![ImmediateMode, ImmediateMode, RelativeMode], operands: vec![2, 3, 20], writes: vec![(20, 5)], rb: 20 },
+            TraceRecord { step: 2, ip: 6, op_code: OpCode::PrintAddress, modes: vec![RelativeMode], operands: vec![5], writes: vec![], rb: 20 },
+            TraceRecord { step: 3, ip: 8, op_code: OpCode::Halt, modes: vec![], operands: vec![], writes: vec![], rb: 20 }
+        ]
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        let bytes = traced(TraceFormat::Binary);
+        assert_eq!(read_binary_trace(&bytes[..]).unwrap(), expected());
+    }
+
+    #[test]
+    fn json_lines_roundtrip() {
+        let bytes = traced(TraceFormat::JsonLines);
+        let text = String::from_utf8(bytes.clone()).unwrap();
+        assert_eq!(text.lines().nth(1), Some(r#"{"step":1,"ip":2,"op":"add","modes":[1,1,2],"operands":[2,3,20],"writes":[[20,5]],"rb":20}"#));
+        assert_eq!(read_json_trace(&bytes[..]).unwrap(), expected());
+    }
+
+    #[test]
+    fn json_lines_that_are_not_records() {
+        let err = read_json_trace(&b"{\"step\":0}\n"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn trace_cut_short() {
+        let bytes = traced(TraceFormat::Binary);
+        assert_eq!(read_binary_trace(&bytes[..5]).unwrap(), vec![]);
+
+        // Inside the step of the first record, then inside the rest of it
+        for &end in [8, 20, bytes.len() - 1].iter() {
+            let err = read_binary_trace(&bytes[..end]).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof, "cut at {}", end);
+        }
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut bytes = traced(TraceFormat::Binary);
+        bytes[4] = VERSION + 1;
+        assert_eq!(read_binary_trace(&bytes[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(read_binary_trace(&b"ICT"[..]).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
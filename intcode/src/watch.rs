@@ -0,0 +1,45 @@
+use std::ops::RangeInclusive;
+
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite
+}
+
+impl Access {
+    fn covers(&self, access: Access) -> bool {
+        *self == Access::ReadWrite || *self == access
+    }
+}
+
+// A data access that hit a watchpoint. For reads old and new are both the value read.
+#[derive(PartialEq, Debug, Clone)]
+pub struct WatchEvent<W = i128> {
+    pub id: usize,
+    pub address: i128,
+    pub position: i128,
+    pub access: Access,
+    pub old: W,
+    pub new: W
+}
+
+pub type WatchCallback<W> = Box<dyn FnMut(&WatchEvent<W>) + Send>;
+
+pub enum WatchAction<W = i128> {
+    Pause,
+    Callback(WatchCallback<W>)
+}
+
+pub struct Watchpoint<W = i128> {
+    pub id: usize,
+    pub positions: RangeInclusive<i128>,
+    pub access: Access,
+    pub action: WatchAction<W>
+}
+
+impl<W> Watchpoint<W> {
+    pub fn matches(&self, position: i128, access: Access) -> bool {
+        self.access.covers(access) && self.positions.contains(&position)
+    }
+}
@@ -0,0 +1,257 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+// What a memory cell holds. Addresses, the instruction pointer and the relative base are always
+// i128, so a word that is used as one of those has to fit.
+pub trait Word: Clone + PartialEq + PartialOrd + fmt::Debug + fmt::Display + FromStr + Send + Sync + 'static {
+    fn from_i32(value: i32) -> Self;
+    fn from_i128(value: i128) -> Option<Self>;
+    fn to_i128(&self) -> Option<i128>;
+
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+    fn wrapping_add(&self, other: &Self) -> Self;
+    fn wrapping_mul(&self, other: &Self) -> Self;
+    fn saturating_add(&self, other: &Self) -> Self;
+    fn saturating_mul(&self, other: &Self) -> Self;
+
+    fn zero() -> Self {
+        Self::from_i32(0)
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == Self::zero()
+    }
+}
+
+macro_rules! primitive_word {
+    ($type:ty) => {
+        impl Word for $type {
+            fn from_i32(value: i32) -> $type {
+                value as $type
+            }
+
+            fn from_i128(value: i128) -> Option<$type> {
+                <$type>::try_from(value).ok()
+            }
+
+            fn to_i128(&self) -> Option<i128> {
+                Some(*self as i128)
+            }
+
+            fn checked_add(&self, other: &$type) -> Option<$type> {
+                <$type>::checked_add(*self, *other)
+            }
+
+            fn checked_mul(&self, other: &$type) -> Option<$type> {
+                <$type>::checked_mul(*self, *other)
+            }
+
+            fn wrapping_add(&self, other: &$type) -> $type {
+                <$type>::wrapping_add(*self, *other)
+            }
+
+            fn wrapping_mul(&self, other: &$type) -> $type {
+                <$type>::wrapping_mul(*self, *other)
+            }
+
+            fn saturating_add(&self, other: &$type) -> $type {
+                <$type>::saturating_add(*self, *other)
+            }
+
+            fn saturating_mul(&self, other: &$type) -> $type {
+                <$type>::saturating_mul(*self, *other)
+            }
+        }
+    };
+}
+
+primitive_word!(i32);
+primitive_word!(i64);
+primitive_word!(i128);
+
+impl Word for BigInt {
+    fn from_i32(value: i32) -> BigInt {
+        BigInt::from(value)
+    }
+
+    fn from_i128(value: i128) -> Option<BigInt> {
+        Some(BigInt::from(value))
+    }
+
+    fn to_i128(&self) -> Option<i128> {
+        ToPrimitive::to_i128(self)
+    }
+
+    // No width to overflow, every policy gives the exact result
+    fn checked_add(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &BigInt) -> Option<BigInt> {
+        Some(self * other)
+    }
+
+    fn wrapping_add(&self, other: &BigInt) -> BigInt {
+        self + other
+    }
+
+    fn wrapping_mul(&self, other: &BigInt) -> BigInt {
+        self * other
+    }
+
+    fn saturating_add(&self, other: &BigInt) -> BigInt {
+        self + other
+    }
+
+    fn saturating_mul(&self, other: &BigInt) -> BigInt {
+        self * other
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+}
+
+// What Add and Multiply do when the result does not fit the word
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum Arithmetic {
+    Wrapping,
+    Saturating,
+    // Stop with IntcodeError::Overflow
+    #[default]
+    Checked
+}
+
+impl Arithmetic {
+    pub fn add<W: Word>(&self, left: &W, right: &W) -> Option<W> {
+        match self {
+            Arithmetic::Wrapping => Some(left.wrapping_add(right)),
+            Arithmetic::Saturating => Some(left.saturating_add(right)),
+            Arithmetic::Checked => left.checked_add(right)
+        }
+    }
+
+    pub fn mul<W: Word>(&self, left: &W, right: &W) -> Option<W> {
+        match self {
+            Arithmetic::Wrapping => Some(left.wrapping_mul(right)),
+            Arithmetic::Saturating => Some(left.saturating_mul(right)),
+            Arithmetic::Checked => left.checked_mul(right)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_program_as, Computer, IntcodeError, OpCode};
+
+    const QUINE: &str = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+    const SQUARE: &str = "1102,34915192,34915192,7,4,7,99,0";
+    const LARGE: &str = "104,1125899906842624,99";
+
+    fn outputs<W: Word>(program: &str) -> Result<Vec<W>, IntcodeError> {
+        let program = parse_program_as::<W>(program).unwrap_or_else(|_| panic!("bad program {}", program));
+        let mut computer = Computer::new(program);
+        computer.run_to_halt()?;
+
+        let mut outputs = Vec::new();
+        while let Some(output) = computer.read_output() {
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+
+    fn words<W: Word>(values: &[i128]) -> Vec<W> {
+        values.iter().map(|&value| W::from_i128(value).unwrap()).collect()
+    }
+
+    fn day_9_examples<W: Word>() {
+        let quine: Vec<i128> = QUINE.split(',').map(|word| word.parse().unwrap()).collect();
+        assert_eq!(outputs::<W>(QUINE), Ok(words(&quine)));
+        assert_eq!(outputs::<W>(SQUARE), Ok(words(&[1219070632396864])));
+        assert_eq!(outputs::<W>(LARGE), Ok(words(&[1125899906842624])));
+    }
+
+    #[test]
+    fn day_9_examples_on_i64() {
+        day_9_examples::<i64>();
+    }
+
+    #[test]
+    fn day_9_examples_on_bigint() {
+        day_9_examples::<BigInt>();
+    }
+
+    #[test]
+    fn bigint_goes_past_i128() {
+        let program = format!("1002,5,{},0,99,{}", i128::MAX, i128::MAX);
+        let mut computer = Computer::new(parse_program_as::<BigInt>(&program).unwrap());
+        computer.run_to_halt().unwrap();
+        assert_eq!(computer.peek(0), BigInt::from(i128::MAX) * BigInt::from(i128::MAX));
+    }
+
+    #[test]
+    fn i32_rejects_words_that_do_not_fit() {
+        let quine: Vec<i128> = QUINE.split(',').map(|word| word.parse().unwrap()).collect();
+        assert_eq!(outputs::<i32>(QUINE), Ok(words(&quine)));
+        // Too big for an i32, so the program does not load
+        assert!(parse_program_as::<i32>(LARGE).is_err());
+    }
+
+    #[test]
+    fn i32_overflow_is_an_error() {
+        assert_eq!(outputs::<i32>(SQUARE), Err(IntcodeError::Overflow {
+            address: 0,
+            op_code: OpCode::Multiply,
+            left: 34915192,
+            right: 34915192
+        }));
+    }
+
+    // A harmless add, then max + 1 at address 4
+    fn add_one_past<W: Word>(max: W, arithmetic: Arithmetic) -> Result<W, IntcodeError> {
+        let mut program = words(&[1101, 0, 0, 10, 1001, 9, 1, 9, 99]);
+        program.extend(vec![max, W::zero()]);
+        let mut computer = Computer::new(program);
+        computer.set_arithmetic(arithmetic);
+        computer.run_to_halt()?;
+        Ok(computer.peek(9))
+    }
+
+    #[test]
+    fn wrapping_at_the_boundary() {
+        assert_eq!(add_one_past(i64::MAX, Arithmetic::Wrapping), Ok(i64::MIN));
+        assert_eq!(add_one_past(i128::MAX, Arithmetic::Wrapping), Ok(i128::MIN));
+        assert_eq!(Arithmetic::Wrapping.mul(&i64::MAX, &2), Some(-2));
+    }
+
+    #[test]
+    fn saturating_at_the_boundary() {
+        assert_eq!(add_one_past(i64::MAX, Arithmetic::Saturating), Ok(i64::MAX));
+        assert_eq!(add_one_past(i128::MAX, Arithmetic::Saturating), Ok(i128::MAX));
+        assert_eq!(Arithmetic::Saturating.mul(&i128::MIN, &2), Some(i128::MIN));
+    }
+
+    #[test]
+    fn checked_at_the_boundary() {
+        assert_eq!(add_one_past(i64::MAX, Arithmetic::Checked), Err(IntcodeError::Overflow {
+            address: 4,
+            op_code: OpCode::Add,
+            left: i64::MAX as i128,
+            right: 1
+        }));
+        assert_eq!(add_one_past(i128::MAX, Arithmetic::Checked), Err(IntcodeError::Overflow {
+            address: 4,
+            op_code: OpCode::Add,
+            left: i128::MAX,
+            right: 1
+        }));
+        // One below the boundary is still fine
+        assert_eq!(add_one_past(i64::MAX - 1, Arithmetic::Checked), Ok(i64::MAX));
+    }
+}
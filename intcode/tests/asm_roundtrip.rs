@@ -0,0 +1,23 @@
+// Assembling the disassembly of every puzzle input has to give back the same program, word for word
+
+use std::fs;
+use std::path::Path;
+
+use intcode::{asm, disasm};
+
+const DAYS: [&str; 8] = ["day_2", "day_5", "day_7", "day_9", "day_11", "day_13", "day_15", "day_17"];
+
+#[test]
+fn disassembly_assembles_back_to_the_program() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+
+    for (day, part) in DAYS.iter().flat_map(|day| vec![(day, "p1"), (day, "p2")]) {
+        let path = root.join(day).join(part).join("input");
+        let text = fs::read_to_string(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+        let program = intcode::parse_program(&text).unwrap();
+
+        let listing: Vec<String> = disasm::disassemble(&program).iter().map(|line| line.text.clone()).collect();
+        let assembled = asm::assemble(&listing.join("\n")).unwrap_or_else(|err| panic!("{}/{}: {}", day, part, err));
+        assert!(assembled == program, "{}/{} does not assemble back to its input", day, part);
+    }
+}
@@ -0,0 +1,135 @@
+// Runs every case in tests/conformance/*.toml. A case gives a program and what running it should
+// do: the outputs it prints, the memory it leaves behind, or for amplifier cases the final signal.
+// Anything left out is not checked.
+
+use std::fs;
+use std::path::Path;
+
+use intcode::{Cluster, Computer, RunState, Topology};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Suite {
+    case: Vec<Case>
+}
+
+// TOML integers are 64 bit, so values are read as i64 and widened
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+    name: String,
+    program: String,
+    #[serde(default)]
+    inputs: Vec<i64>,
+    outputs: Option<Vec<i64>>,
+    // Compared from address 0 for as many cells as are given
+    memory: Option<String>,
+    // One amplifier per phase setting, like day 7
+    phases: Option<Vec<i64>>,
+    #[serde(default)]
+    feedback: bool,
+    signal: Option<i64>
+}
+
+impl Case {
+    fn run(&self) -> Result<(), String> {
+        let program = intcode::parse_program(&self.program).map_err(|err| format!("bad program: {}", err))?;
+
+        match &self.phases {
+            Some(phases) => self.run_amplifiers(program, phases),
+            None => self.run_single(program)
+        }
+    }
+
+    fn run_single(&self, program: Vec<i128>) -> Result<(), String> {
+        let mut computer = Computer::new(program);
+        for &input in self.inputs.iter() {
+            computer.push_input(input as i128);
+        }
+
+        let state = computer.run().map_err(|err| err.to_string())?;
+        if state != RunState::Halted {
+            return Err(format!("stopped with {:?}", state));
+        }
+
+        let mut outputs = Vec::new();
+        while let Some(output) = computer.read_output() {
+            outputs.push(output);
+        }
+        if let Some(expected) = &self.outputs {
+            let expected: Vec<i128> = expected.iter().map(|&value| value as i128).collect();
+            if outputs != expected {
+                return Err(format!("printed {:?}, expected {:?}", outputs, expected));
+            }
+        }
+
+        if let Some(expected) = &self.memory {
+            let expected = intcode::parse_program(expected).map_err(|err| format!("bad memory: {}", err))?;
+            let memory: Vec<i128> = (0..expected.len() as i128).map(|pos| computer.peek(pos)).collect();
+            if memory != expected {
+                return Err(format!("memory is {:?}, expected {:?}", memory, expected));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn run_amplifiers(&self, program: Vec<i128>, phases: &[i64]) -> Result<(), String> {
+        let machines = phases.iter().map(|_| Computer::new(program.clone())).collect();
+        let topology = if self.feedback { Topology::Ring } else { Topology::Chain };
+
+        let mut cluster = Cluster::new(machines, topology);
+        for (machine, &phase) in phases.iter().enumerate() {
+            cluster.push_input(machine, phase as i128);
+        }
+        cluster.push_input(0, 0);
+
+        let run = cluster.run();
+        for (machine, state) in run.states.iter().enumerate() {
+            match state {
+                Ok(RunState::Halted) => {},
+                Ok(state) => return Err(format!("amplifier {} stopped with {:?}", machine, state)),
+                Err(err) => return Err(format!("amplifier {}: {}", machine, err))
+            }
+        }
+
+        let signal = run.last_output(phases.len() - 1).copied();
+        if let Some(expected) = self.signal {
+            if signal != Some(expected as i128) {
+                return Err(format!("signal was {:?}, expected {}", signal, expected));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn conformance() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .expect("tests/conformance is missing")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .collect();
+    files.sort();
+    assert!(!files.is_empty(), "no cases in {}", dir.display());
+
+    // Runs everything before failing so one broken case does not hide the rest
+    let mut failures = Vec::new();
+    let mut count = 0;
+    for file in files {
+        let name = file.file_name().unwrap().to_string_lossy().into_owned();
+        let text = fs::read_to_string(&file).unwrap();
+        let suite: Suite = toml::from_str(&text).unwrap_or_else(|err| panic!("{}: {}", name, err));
+
+        for case in suite.case {
+            count += 1;
+            if let Err(err) = case.run() {
+                failures.push(format!("{}: {}: {}", name, case.name, err));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "{} of {} cases failed:\n{}", failures.len(), count, failures.join("\n"));
+}